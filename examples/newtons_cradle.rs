@@ -0,0 +1,34 @@
+use vulkys::{
+    core::parse::parse_scene_file,
+    physics::scene::{Backend, Scene, SceneInfo},
+};
+
+fn main() -> anyhow::Result<()> {
+    let log_file = std::fs::OpenOptions::new()
+        .write(true)
+        .truncate(true)
+        .create(true)
+        .open("run.log")?;
+    tracing_subscriber::fmt()
+        .event_format(
+            tracing_subscriber::fmt::format()
+                .with_line_number(true)
+                .with_ansi(false)
+                .with_source_location(true)
+                .with_file(true),
+        )
+        .with_file(true)
+        .with_writer(log_file)
+        .init();
+
+    // Five balls hanging from fixed pivots by distance constraints
+    let scene_info = SceneInfo {
+        backend: Backend::Cpu,
+        ..parse_scene_file("examples/scenes/newtons_cradle.json")?
+    };
+    let scene: Scene = Scene::with_info(scene_info);
+    // Running simulation
+    scene.run();
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use vulkys::physics::replay::{ReplayPlayer, ReplayRecording};
+
+// Usage: cargo r --example replay -- <recording.json | recording.bin>
+fn main() -> anyhow::Result<()> {
+    tracing_subscriber::fmt().init();
+
+    let path = std::env::args()
+        .nth(1)
+        .ok_or_else(|| anyhow::anyhow!("expected the path of a recording"))?;
+    let recording = ReplayRecording::load(&path)?;
+    let total_steps = recording.checksums.len();
+
+    let mut player = ReplayPlayer::new(recording);
+    match player.run() {
+        Ok(()) => println!("replayed {} steps without diverging", total_steps),
+        Err(divergence) => println!(
+            "{} ({} of {} steps matched)",
+            divergence,
+            player.get_steps_replayed() - 1,
+            total_steps
+        ),
+    }
+
+    Ok(())
+}
@@ -27,39 +27,34 @@ fn main() -> anyhow::Result<()> {
         .init();
 
     // Initialize objects
-    let circle_1: RigidBody = RigidBody::Circle_(
-        Circle {
-            radius: 0.25,
-            position: FVec2::new(0.5, 0.5),
-            velocity: FVec2::new(-1.5, -1.0),
-            color: Color32::from_hex("#23F0C7").unwrap(),
-        },
-        0,
-    );
-    let circle_2: RigidBody = RigidBody::Circle_(
-        Circle {
-            radius: 0.25,
-            position: FVec2::new(-0.5, 0.65),
-            velocity: FVec2::new(1.5, 0.),
-            color: Color32::from_hex("#EF767A").unwrap(),
-        },
-        1,
-    );
-    let circle_3: RigidBody = RigidBody::Circle_(
-        Circle {
-            radius: 0.3,
-            position: FVec2::new(0., -0.5),
-            velocity: FVec2::new(2.75, 2.6),
-            color: Color32::from_hex("#7D7ABC").unwrap(),
-        },
-        2,
-    );
+    let circle_1: RigidBody = RigidBody::from(Circle {
+        radius: 0.25,
+        position: FVec2::new(0.5, 0.5),
+        velocity: FVec2::new(-1.5, -1.0),
+        color: Color32::from_hex("#23F0C7").unwrap(),
+        ..Default::default()
+    });
+    let circle_2: RigidBody = RigidBody::from(Circle {
+        radius: 0.25,
+        position: FVec2::new(-0.5, 0.65),
+        velocity: FVec2::new(1.5, 0.),
+        color: Color32::from_hex("#EF767A").unwrap(),
+        ..Default::default()
+    });
+    let circle_3: RigidBody = RigidBody::from(Circle {
+        radius: 0.3,
+        position: FVec2::new(0., -0.5),
+        velocity: FVec2::new(2.75, 2.6),
+        color: Color32::from_hex("#7D7ABC").unwrap(),
+        ..Default::default()
+    });
     let objects: Vec<RigidBody> = vec![circle_1, circle_2, circle_3];
     // Initialize scene
     let scene_info = SceneInfo {
         objects,
         dt: 1e-3,
         gravity: 20.0,
+        ..Default::default()
     };
     let scene: Scene = Scene::with_info(scene_info);
     // Running simulation
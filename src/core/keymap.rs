@@ -0,0 +1,185 @@
+use std::{collections::BTreeMap, path::Path};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+use tracing::{info, warn};
+use winit::event::VirtualKeyCode;
+
+#[doc = "Where the window looks for a keymap, relative to the working directory"]
+pub const DEFAULT_KEYMAP_PATH: &str = "keymap.json";
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Action {
+    Quit,
+    Pause,
+    Resume,
+    StepOnce,
+    StepBatch,
+    RunUntilCollision,
+    SlowDown,
+    SpeedUp,
+    RewindKeyframe,
+    ForwardKeyframe,
+    RewindFar,
+    ForwardFar,
+    ToggleFollow,
+    ResetCamera,
+    ToggleFullscreen,
+    ToggleHelp,
+}
+
+impl Action {
+    pub fn get_description(&self) -> &'static str {
+        match self {
+            Action::Quit => "quit",
+            Action::Pause => "pause",
+            Action::Resume => "resume",
+            Action::StepOnce => "step once",
+            Action::StepBatch => "step a batch",
+            Action::RunUntilCollision => "run until collision",
+            Action::SlowDown => "slower",
+            Action::SpeedUp => "faster",
+            Action::RewindKeyframe => "back one keyframe",
+            Action::ForwardKeyframe => "forward one keyframe",
+            Action::RewindFar => "back ten keyframes",
+            Action::ForwardFar => "forward ten keyframes",
+            Action::ToggleFollow => "follow body under cursor",
+            Action::ResetCamera => "reset camera",
+            Action::ToggleFullscreen => "toggle fullscreen",
+            Action::ToggleHelp => "show or hide help",
+        }
+    }
+}
+
+#[doc = "Which keys trigger which action. Keys are winit's layout-aware virtual key codes, so bindings follow what is printed on the key. In JSON an action maps to a list of key names, e.g. `{\"Pause\": [\"P\", \"Space\"]}`."]
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Keymap {
+    bindings: BTreeMap<Action, Vec<VirtualKeyCode>>,
+}
+
+impl Default for Keymap {
+    fn default() -> Self {
+        let bindings = [
+            (Action::Quit, VirtualKeyCode::Q),
+            (Action::Pause, VirtualKeyCode::P),
+            (Action::Resume, VirtualKeyCode::R),
+            (Action::StepOnce, VirtualKeyCode::N),
+            (Action::StepBatch, VirtualKeyCode::M),
+            (Action::RunUntilCollision, VirtualKeyCode::C),
+            (Action::SlowDown, VirtualKeyCode::Minus),
+            (Action::SpeedUp, VirtualKeyCode::Equals),
+            (Action::RewindKeyframe, VirtualKeyCode::Comma),
+            (Action::ForwardKeyframe, VirtualKeyCode::Period),
+            (Action::RewindFar, VirtualKeyCode::LBracket),
+            (Action::ForwardFar, VirtualKeyCode::RBracket),
+            (Action::ToggleFollow, VirtualKeyCode::F),
+            (Action::ResetCamera, VirtualKeyCode::Key0),
+            (Action::ToggleFullscreen, VirtualKeyCode::F11),
+            (Action::ToggleHelp, VirtualKeyCode::H),
+        ];
+        Self {
+            bindings: bindings
+                .into_iter()
+                .map(|(action, key)| (action, vec![key]))
+                .collect(),
+        }
+    }
+}
+
+impl Keymap {
+    pub fn get_action(&self, key: VirtualKeyCode) -> Option<Action> {
+        self.bindings
+            .iter()
+            .find(|(_, keys)| keys.contains(&key))
+            .map(|(action, _)| *action)
+    }
+    pub fn get_keys(&self, action: Action) -> &[VirtualKeyCode] {
+        self.bindings.get(&action).map_or(&[], |keys| keys)
+    }
+    pub fn get_bindings(&self) -> &BTreeMap<Action, Vec<VirtualKeyCode>> {
+        &self.bindings
+    }
+    #[doc = "Binds `keys` to `action`, replacing its keys and taking them away from any other action"]
+    pub fn bind(&mut self, action: Action, keys: Vec<VirtualKeyCode>) {
+        for other_keys in self.bindings.values_mut() {
+            other_keys.retain(|key| !keys.contains(key));
+        }
+        self.bindings.insert(action, keys);
+    }
+
+    #[doc = "Reads a keymap. Actions the file leaves out keep their default keys, except those the file binds elsewhere. A key bound to two actions in the file is an error."]
+    pub fn parse_str(json: &str) -> anyhow::Result<Self> {
+        let overrides: BTreeMap<Action, Vec<VirtualKeyCode>> = serde_json::from_str(json)?;
+        let mut bound_keys: BTreeMap<VirtualKeyCode, Action> = BTreeMap::new();
+        for (action, keys) in &overrides {
+            for key in keys {
+                if let Some(other) = bound_keys.insert(*key, *action) {
+                    if other != *action {
+                        bail!("{:?} is bound to both {:?} and {:?}", key, other, action);
+                    }
+                }
+            }
+        }
+        let mut keymap = Self::default();
+        for (action, keys) in overrides {
+            keymap.bind(action, keys);
+        }
+        Ok(keymap)
+    }
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        std::fs::write(path, serde_json::to_string_pretty(&self.bindings)?)?;
+        Ok(())
+    }
+    #[doc = "Loads the keymap at `path`, falling back to the defaults when it is missing or invalid"]
+    pub fn load_or_default(path: impl AsRef<Path>) -> Self {
+        let path = path.as_ref();
+        let json = match std::fs::read_to_string(path) {
+            Ok(json) => json,
+            Err(_) => {
+                info!("no keymap at {}, using the default keys", path.display());
+                return Self::default();
+            }
+        };
+        match Self::parse_str(&json) {
+            Ok(keymap) => keymap,
+            Err(e) => {
+                warn!(
+                    "invalid keymap at {}: {}; using the default keys",
+                    path.display(),
+                    e
+                );
+                Self::default()
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Action, Keymap};
+    use winit::event::VirtualKeyCode;
+
+    #[test]
+    fn check_overrides_keep_other_defaults() {
+        let keymap = Keymap::parse_str(r#"{"Pause": ["Space", "P"]}"#).unwrap();
+
+        assert_eq!(
+            keymap.get_action(VirtualKeyCode::Space),
+            Some(Action::Pause)
+        );
+        assert_eq!(keymap.get_action(VirtualKeyCode::P), Some(Action::Pause));
+        assert_eq!(keymap.get_action(VirtualKeyCode::Q), Some(Action::Quit));
+        assert_eq!(keymap.get_action(VirtualKeyCode::Z), None);
+        assert!(Keymap::parse_str(r#"{"Fly": ["F"]}"#).is_err());
+    }
+
+    #[test]
+    fn check_overrides_take_keys_from_defaults() {
+        let keymap = Keymap::parse_str(r#"{"Pause": ["Q"]}"#).unwrap();
+
+        assert_eq!(keymap.get_action(VirtualKeyCode::Q), Some(Action::Pause));
+        assert!(keymap.get_keys(Action::Quit).is_empty());
+        assert_eq!(keymap.get_action(VirtualKeyCode::P), None);
+        assert!(Keymap::parse_str(r#"{"Pause": ["Q"], "Resume": ["Q"]}"#).is_err());
+    }
+}
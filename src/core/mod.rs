@@ -1,2 +1,3 @@
 pub mod data_structures;
+pub mod keymap;
 pub mod parse;
@@ -1,78 +1,122 @@
-// use ecolor::Color32;
-// use serde_json::Value;
+use std::path::Path;
 
-// use crate::{
-//     physics::{circle::Circle, rigidbody::RigidBody},
-//     FVec2,
-// };
+use serde::{Deserialize, Serialize};
 
-// pub fn parse_serde_value(json: Value) -> Result<Vec<RigidBody>, std::io::Error> {
-//     let objects_arr = &json;
-//     let mut objects: Vec<RigidBody> = Vec::new();
+use crate::physics::{
+    constraint::Constraint,
+    rigidbody::{RBid, RigidBody},
+    scene::SceneInfo,
+    sensor::Trigger,
+};
 
-//     if let Some(objects_arr) = objects_arr.as_object() {
-//         for object in objects_arr.into_iter() {
-//             let inner_objects = object.1;
-//             for object_struct in inner_objects.as_array().unwrap() {
-//                 let object_types: Vec<&String> =
-//                     object_struct.as_object().unwrap().keys().collect();
-//                 object_types.iter().for_each(|k| {
-//                     let Some(rigidbody) =
-//                         rigidbody_from_value(k, object_struct[k].as_array().unwrap())
-//                     else {
-//                         return;
-//                     };
-//                     objects.push(rigidbody);
-//                 })
-//             }
-//         }
-//     }
+#[doc = "JSON description of a scene. Only `objects` is required; everything else falls back to `SceneInfo::default()`."]
+#[derive(Serialize, Deserialize, Default)]
+pub struct SceneFile {
+    pub objects: Vec<RigidBody>,
+    #[serde(default)]
+    pub constraints: Vec<Constraint>,
+    #[serde(default)]
+    pub triggers: Vec<Trigger>,
+    #[serde(default)]
+    pub never_collide: Vec<(RBid, RBid)>,
+    #[serde(default)]
+    pub dt: Option<f32>,
+    #[serde(default)]
+    pub gravity: Option<f32>,
+}
 
-//     Ok(objects)
-// }
+impl SceneFile {
+    pub fn into_scene_info(self) -> SceneInfo {
+        let default_info = SceneInfo::default();
+        SceneInfo {
+            objects: self.objects,
+            constraints: self.constraints,
+            triggers: self.triggers,
+            never_collide: self.never_collide,
+            dt: self.dt.unwrap_or(default_info.dt),
+            gravity: self.gravity.unwrap_or(default_info.gravity),
+            described_ids: true,
+            ..default_info
+        }
+    }
+}
 
-// fn rigidbody_from_value(object_type: &String, json: &Vec<Value>) -> Option<RigidBody> {
-//     let rbid = match json[1].clone().as_number() {
-//         Some(num) => num.as_u64().unwrap() as u8,
-//         _ => return None,
-//     };
-//     let json = json[0].clone();
-//     match &object_type[..] {
-//         "Circle_" => {
-//             let position_object = json["position"].as_object().unwrap();
-//             let (x, y) = (
-//                 position_object["x"].as_number().unwrap(),
-//                 position_object["y"].as_number().unwrap(),
-//             );
-//             let position: FVec2 =
-//                 FVec2::new(x.as_f64().unwrap() as f32, y.as_f64().unwrap() as f32);
+pub fn parse_scene_str(json: &str) -> anyhow::Result<SceneInfo> {
+    let scene_file: SceneFile = serde_json::from_str(json)?;
+    Ok(scene_file.into_scene_info())
+}
 
-//             let velocity_object = json["velocity"].as_object().unwrap();
-//             let (x, y) = (
-//                 velocity_object["x"].as_number().unwrap(),
-//                 velocity_object["y"].as_number().unwrap(),
-//             );
-//             let velocity: FVec2 =
-//                 FVec2::new(x.as_f64().unwrap() as f32, y.as_f64().unwrap() as f32);
+pub fn parse_scene_file(path: impl AsRef<Path>) -> anyhow::Result<SceneInfo> {
+    let json = std::fs::read_to_string(path)?;
+    parse_scene_str(&json)
+}
 
-//             let color = json["color"].as_object().unwrap();
-//             let (r, g, b) = (
-//                 color["r"].as_u64().unwrap(),
-//                 color["g"].as_f64().unwrap(),
-//                 color["b"].as_u64().unwrap(),
-//             );
-//             let color = Color32::from_rgb(r as u8, g as u8, b as u8);
+#[cfg(test)]
+mod tests {
+    use super::parse_scene_str;
+    use crate::{
+        physics::{
+            constraint::{Anchor, ConstraintKind},
+            scene::Scene,
+        },
+        FVec2,
+    };
 
-//             let radius = json["radius"].as_number().unwrap().as_f64().unwrap() as f32;
+    #[test]
+    fn check_parsing_constraints() {
+        let json = r#"{
+            "objects": [
+                {"Circle_": [{"radius": 0.1, "position": {"x": 0.0, "y": 0.5}, "velocity": {"x": 1.0, "y": 0.0}, "color": [255, 255, 255, 255]}, 0]},
+                {"Circle_": [{"radius": 0.1, "position": {"x": 0.3, "y": 0.5}, "velocity": {"x": 0.0, "y": 0.0}, "color": [255, 0, 0, 255]}, 1]}
+            ],
+            "constraints": [
+                {"body": 0, "anchor": {"World": {"x": 0.0, "y": 0.0}}, "kind": {"Distance": {"length": 0.5}}},
+                {"body": 0, "anchor": {"Body": 1}, "kind": {"Spring": {"rest_length": 0.3, "stiffness": 10.0, "damping": 0.1}}}
+            ],
+            "gravity": 9.8
+        }"#;
+        let scene_info = parse_scene_str(json).unwrap();
 
-//             let circle = Circle {
-//                 position,
-//                 velocity,
-//                 radius,
-//                 color,
-//             };
-//             return Some(RigidBody::Circle_(circle, rbid));
-//         }
-//         _ => None,
-//     }
-// }
+        assert_eq!(scene_info.objects.len(), 2);
+        assert_eq!(scene_info.gravity, 9.8);
+        assert_eq!(
+            scene_info.constraints[0].anchor,
+            Anchor::World(FVec2::new(0., 0.))
+        );
+        assert_eq!(
+            scene_info.constraints[1].kind,
+            ConstraintKind::Spring {
+                rest_length: 0.3,
+                stiffness: 10.,
+                damping: 0.1
+            }
+        );
+    }
+
+    #[test]
+    fn check_out_of_order_ids_keep_constraint_endpoints() {
+        let json = r#"{
+            "objects": [
+                {"Circle_": [{"radius": 0.1, "position": {"x": 0.3, "y": 0.5}, "velocity": {"x": 0.0, "y": 0.0}, "color": [255, 0, 0, 255]}, 1]},
+                {"Circle_": [{"radius": 0.1, "position": {"x": 0.0, "y": 0.5}, "velocity": {"x": 0.0, "y": 0.0}, "color": [255, 255, 255, 255]}, 0]}
+            ],
+            "constraints": [
+                {"body": 0, "anchor": {"Body": 1}, "kind": {"Distance": {"length": 0.3}}},
+                {"body": 0, "anchor": {"Body": 2}, "kind": {"Distance": {"length": 0.3}}}
+            ]
+        }"#;
+        let scene = Scene::with_info(parse_scene_str(json).unwrap());
+        let constraints = scene.get_constraints();
+        let Anchor::Body(anchor) = constraints[0].anchor else {
+            panic!("expected a body anchor, got {:?}", constraints[0].anchor);
+        };
+
+        // NOTE: The constraint naming a missing body is dropped.
+        assert_eq!(constraints.len(), 1);
+        assert_eq!(
+            scene.body(constraints[0].body).unwrap().get_position().x,
+            0.
+        );
+        assert_eq!(scene.body(anchor).unwrap().get_position().x, 0.3);
+    }
+}
@@ -6,6 +6,7 @@ use std::ops::{Add, AddAssign, Mul, Sub};
 use ecolor::Color32;
 use libm::atan2f;
 use renderer::vk_core::CustomVertex;
+use serde::{Deserialize, Serialize};
 use vulkano::buffer::BufferContents;
 
 pub mod core;
@@ -15,7 +16,7 @@ pub mod renderer;
 
 const WINDOW_LENGTH: f32 = 1000.;
 
-#[derive(Clone, Copy, Debug, PartialEq, BufferContents)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, BufferContents, Serialize, Deserialize)]
 #[repr(C)]
 pub struct FVec2 {
     x: f32,
@@ -87,6 +88,24 @@ impl FVec2 {
     pub fn get_polar_angle(&self) -> f32 {
         atan2f(self.y, self.x)
     }
+    #[doc = "z-component of the 3D cross product"]
+    pub fn cross(&self, other: FVec2) -> f32 {
+        self.x * other.y - self.y * other.x
+    }
+    #[doc = "Rotated a quarter turn, so that `w * r.perpendicular()` is the velocity of a point at `r` on a body spinning at `w`"]
+    pub fn perpendicular(&self) -> Self {
+        Self {
+            x: -self.y,
+            y: self.x,
+        }
+    }
+    pub fn rotate(&self, angle: f32) -> Self {
+        let (sin, cos) = angle.sin_cos();
+        Self {
+            x: self.x * cos - self.y * sin,
+            y: self.x * sin + self.y * cos,
+        }
+    }
 }
 
 impl From<&[f32; 2]> for FVec2 {
@@ -0,0 +1,152 @@
+use std::ops::Deref;
+
+use serde::{Deserialize, Serialize};
+
+use super::rigidbody::{RBid, RigidBody};
+
+#[derive(Clone, Copy, Debug, Serialize, Deserialize)]
+struct Slot {
+    generation: u32,
+    dense_index: Option<usize>,
+}
+
+#[doc = "Owns the bodies of a scene and hands out generational `RBid`s. Bodies are packed, and a body's position in the packed list is its slot in the GPU buffers. Despawning moves the last body into the freed slot, so a live handle's GPU slot can change; the scene rebuilds the GPU buffers whenever that happens."]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct BodyArena {
+    bodies: Vec<RigidBody>,
+    slots: Vec<Slot>,
+    free_slots: Vec<u32>,
+}
+
+impl BodyArena {
+    #[doc = "Stores `body` under a new handle, overwriting whatever id it carried"]
+    pub fn insert(&mut self, mut body: RigidBody) -> RBid {
+        let index = match self.free_slots.pop() {
+            Some(index) => index,
+            None => {
+                self.slots.push(Slot {
+                    generation: 0,
+                    dense_index: None,
+                });
+                (self.slots.len() - 1) as u32
+            }
+        };
+        let slot = &mut self.slots[index as usize];
+        slot.dense_index = Some(self.bodies.len());
+        let id = RBid::new(index, slot.generation);
+        body.set_id(id);
+        self.bodies.push(body);
+        id
+    }
+    #[doc = "Removes a body. Its handle, and every copy of it, is stale from then on. The last body takes over the freed GPU slot."]
+    pub fn remove(&mut self, id: RBid) -> Option<RigidBody> {
+        let dense_index = self.get_slot(id)?;
+        let body = self.bodies.swap_remove(dense_index);
+        if let Some(moved) = self.bodies.get(dense_index) {
+            self.slots[moved.get_id().get_index() as usize].dense_index = Some(dense_index);
+        }
+        let slot = &mut self.slots[id.get_index() as usize];
+        slot.dense_index = None;
+        // NOTE: A slot whose generation would wrap is retired rather than reused, so the oldest
+        // handles to it can never become valid again.
+        if let Some(generation) = slot.generation.checked_add(1) {
+            slot.generation = generation;
+            self.free_slots.push(id.get_index());
+        }
+        Some(body)
+    }
+    #[doc = "Position of a live body in the packed list, which is also its GPU buffer slot. Stale handles return `None`."]
+    pub fn get_slot(&self, id: RBid) -> Option<usize> {
+        let slot = self.slots.get(id.get_index() as usize)?;
+        match slot.generation == id.get_generation() {
+            true => slot.dense_index,
+            false => None,
+        }
+    }
+    pub fn contains(&self, id: RBid) -> bool {
+        self.get_slot(id).is_some()
+    }
+    pub fn get(&self, id: RBid) -> Option<&RigidBody> {
+        self.get_slot(id)
+            .map(|dense_index| &self.bodies[dense_index])
+    }
+    pub fn get_mut(&mut self, id: RBid) -> Option<&mut RigidBody> {
+        self.get_slot(id)
+            .map(|dense_index| &mut self.bodies[dense_index])
+    }
+    #[doc = "The packed bodies, for the solvers to step in place. Moving bodies around in it would break handle lookups, so outside the crate bodies are only reached mutably through `get_mut`."]
+    pub(crate) fn bodies_mut(&mut self) -> &mut [RigidBody] {
+        &mut self.bodies
+    }
+}
+
+impl Deref for BodyArena {
+    type Target = [RigidBody];
+
+    fn deref(&self) -> &Self::Target {
+        &self.bodies
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::BodyArena;
+    use crate::physics::{circle::Circle, rigidbody::RigidBody};
+
+    #[test]
+    fn check_stale_handles_and_slots() {
+        let mut arena = BodyArena::default();
+        let first = arena.insert(RigidBody::from(Circle::default()));
+        let second = arena.insert(RigidBody::from(Circle::default()));
+        let third = arena.insert(RigidBody::from(Circle::default()));
+
+        assert!(arena.remove(first).is_some());
+        assert!(!arena.contains(first));
+        assert!(arena.remove(first).is_none());
+        // NOTE: The last body fills the freed slot.
+        assert_eq!(arena.get_slot(third), Some(0));
+        assert_eq!(arena.get_slot(second), Some(1));
+
+        let reused = arena.insert(RigidBody::from(Circle::default()));
+        assert_eq!(reused.get_index(), first.get_index());
+        assert_ne!(reused, first);
+        assert!(arena.get(first).is_none());
+        assert_eq!(arena.get(reused).unwrap().get_id(), reused);
+    }
+
+    #[test]
+    fn check_moved_body_keeps_its_handle() {
+        let mut arena = BodyArena::default();
+        let ids: Vec<_> = (0..3)
+            .map(|radius| {
+                arena.insert(RigidBody::from(Circle {
+                    radius: radius as f32,
+                    ..Default::default()
+                }))
+            })
+            .collect();
+
+        arena.remove(ids[0]);
+        // NOTE: The last body moved into slot 0, but its handle still finds it.
+        assert_eq!(arena.get_slot(ids[2]), Some(0));
+        assert_eq!(arena.get(ids[2]).unwrap().get_radius(), 2.);
+        assert_eq!(arena[0].get_id(), ids[2]);
+        assert_eq!(arena.get(ids[1]).unwrap().get_radius(), 1.);
+    }
+
+    #[test]
+    fn check_exhausted_slot_is_retired() {
+        let mut arena = BodyArena::default();
+        let first = arena.insert(RigidBody::from(Circle::default()));
+        arena.remove(first);
+        arena.slots[0].generation = u32::MAX;
+        let last = arena.insert(RigidBody::from(Circle::default()));
+        assert_eq!(last.get_index(), 0);
+
+        arena.remove(last);
+        let next = arena.insert(RigidBody::from(Circle::default()));
+        assert_eq!(next.get_index(), 1);
+        assert!(!arena.contains(last));
+        assert!(!arena.contains(first));
+    }
+}
@@ -0,0 +1,276 @@
+use crate::FVec2;
+
+use super::{
+    collision::PairFilter,
+    lib::{CCD_MOTION_THRESHOLD, COEFF_RESTITUTION, RESTITUTION_VELOCITY_THRESHOLD, WORLD_BOUNDS},
+    rigidbody::RigidBody,
+};
+
+#[doc = "Whether a body moves far enough in one step, relative to its radius, that overlap tests at the end of the step could miss a contact"]
+pub fn needs_ccd(body: &RigidBody, dt: f32) -> bool {
+    body.is_bullet()
+        || body.get_velocity().magnitude() * dt > CCD_MOTION_THRESHOLD * body.get_radius()
+}
+
+#[doc = "Fraction of the step, in [0, 1], at which two moving circles first touch. Pairs that already overlap or are separating return `None`."]
+pub fn circle_time_of_impact(
+    position_a: FVec2,
+    displacement_a: FVec2,
+    radius_a: f32,
+    position_b: FVec2,
+    displacement_b: FVec2,
+    radius_b: f32,
+) -> Option<f32> {
+    // NOTE: Solves |d + w t| = r_a + r_b for the relative offset d and relative displacement w.
+    let offset = position_b - position_a;
+    let relative_displacement = displacement_b - displacement_a;
+    let radius_sum = radius_a + radius_b;
+
+    let a = relative_displacement.dot(relative_displacement);
+    let b = 2. * offset.dot(relative_displacement);
+    let c = offset.dot(offset) - radius_sum * radius_sum;
+    if c <= 0. || b >= 0. || a <= f32::EPSILON {
+        return None;
+    }
+    let discriminant = b * b - 4. * a * c;
+    if discriminant < 0. {
+        return None;
+    }
+    let time_of_impact = (-b - discriminant.sqrt()) / (2. * a);
+    (0. ..=1.)
+        .contains(&time_of_impact)
+        .then_some(time_of_impact)
+}
+
+#[doc = "Fraction of the step, in [0, 1], at which a moving circle first touches one of the `WORLD_BOUNDS` walls"]
+pub fn wall_time_of_impact(position: FVec2, displacement: FVec2, radius: f32) -> Option<f32> {
+    let axes = [
+        (position.x, displacement.x, &WORLD_BOUNDS.0),
+        (position.y, displacement.y, &WORLD_BOUNDS.1),
+    ];
+    axes.into_iter()
+        .filter_map(|(start, delta, bounds)| {
+            let limit = if delta > 0. {
+                bounds.end - radius - start
+            } else if delta < 0. {
+                bounds.start + radius - start
+            } else {
+                return None;
+            };
+            // NOTE: A body already past the wall is left to the discrete world collision.
+            let time_of_impact = limit / delta;
+            (0. ..=1.)
+                .contains(&time_of_impact)
+                .then_some(time_of_impact)
+        })
+        .reduce(f32::min)
+}
+
+#[doc = "First contact of a swept body within a step"]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Impact {
+    #[doc = "Fraction of the step, in [0, 1], at which the contact begins"]
+    pub time: f32,
+    #[doc = "Unit normal from the body towards what it touches"]
+    pub normal: FVec2,
+    #[doc = "Index of the body touched, or `None` for a wall"]
+    pub other: Option<usize>,
+}
+
+#[doc = "Earliest contact of `objects[idx]` within the step, assuming every body moves with its current velocity. Bodies flagged in `swept` are checked against walls and every body; the others only against swept bodies, so both ends of a fast contact stop at it."]
+pub fn earliest_impact(
+    objects: &[RigidBody],
+    idx: usize,
+    dt: f32,
+    pair_filter: &PairFilter,
+    swept: &[bool],
+) -> Option<Impact> {
+    let body = &objects[idx];
+    let displacement = body.get_velocity() * dt;
+    let mut earliest: Option<Impact> = None;
+    let mut keep_earliest = |impact: Impact| {
+        if earliest.is_none_or(|earliest| impact.time < earliest.time) {
+            earliest = Some(impact);
+        }
+    };
+    if swept[idx] && body.get_collision_filter().collides_with_world() {
+        // NOTE: Each axis is swept on its own so the wall that is hit gives the normal.
+        let axes = [
+            (
+                FVec2::new(displacement.x, 0.),
+                FVec2::new(displacement.x.signum(), 0.),
+            ),
+            (
+                FVec2::new(0., displacement.y),
+                FVec2::new(0., displacement.y.signum()),
+            ),
+        ];
+        for (axis_displacement, normal) in axes {
+            if let Some(time) =
+                wall_time_of_impact(body.get_position(), axis_displacement, body.get_radius())
+            {
+                keep_earliest(Impact {
+                    time,
+                    normal,
+                    other: None,
+                });
+            }
+        }
+    }
+    for (other_idx, other) in objects.iter().enumerate() {
+        // NOTE: Sensors never stop anything, so they are not swept against.
+        if other_idx == idx
+            || !(swept[idx] || swept[other_idx])
+            || body.is_sensor()
+            || other.is_sensor()
+            || !pair_filter.should_collide(body, other)
+        {
+            continue;
+        }
+        let other_displacement = other.get_velocity() * dt;
+        let Some(time) = circle_time_of_impact(
+            body.get_position(),
+            displacement,
+            body.get_radius(),
+            other.get_position(),
+            other_displacement,
+            other.get_radius(),
+        ) else {
+            continue;
+        };
+        let offset = (other.get_position() + other_displacement * time)
+            - (body.get_position() + displacement * time);
+        keep_earliest(Impact {
+            time,
+            normal: offset.get_unit(),
+            other: Some(other_idx),
+        });
+    }
+
+    earliest
+}
+
+#[doc = "Velocity of `objects[idx]` right after the contact at `impact`. The approach along the normal is stopped, or bounced with `COEFF_RESTITUTION` when it is fast enough, as in the contact solver; the motion along the contact is kept."]
+pub fn velocity_after_impact(objects: &[RigidBody], idx: usize, impact: &Impact) -> FVec2 {
+    let body = &objects[idx];
+    let (other_velocity, other_inverse_mass) = match impact.other {
+        Some(other_idx) => (
+            objects[other_idx].get_velocity(),
+            objects[other_idx].get_inverse_mass(),
+        ),
+        None => (FVec2::default(), 0.),
+    };
+    let inverse_mass_sum = body.get_inverse_mass() + other_inverse_mass;
+    let approach_speed = (body.get_velocity() - other_velocity).dot(impact.normal);
+    if approach_speed <= 0. || inverse_mass_sum <= 0. {
+        return body.get_velocity();
+    }
+    let restitution = match approach_speed > RESTITUTION_VELOCITY_THRESHOLD {
+        true => COEFF_RESTITUTION,
+        false => 0.,
+    };
+    let impulse = (1. + restitution) * approach_speed / inverse_mass_sum;
+    body.get_velocity() - impact.normal * (impulse * body.get_inverse_mass())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{circle_time_of_impact, wall_time_of_impact};
+    use crate::{
+        physics::{
+            circle::Circle,
+            event::CollisionPhase,
+            lib::DELTA_TIME,
+            rigidbody::RigidBody,
+            solver::ContactTarget,
+            test_util::{cpu_scene, moving_circle},
+        },
+        FVec2,
+    };
+
+    #[test]
+    fn check_fast_circle_does_not_tunnel() {
+        // NOTE: Moves 1.0 in a single step past a body of radius 0.05; the end positions do not overlap.
+        let time_of_impact = circle_time_of_impact(
+            FVec2::new(-0.5, 0.),
+            FVec2::new(1., 0.),
+            0.01,
+            FVec2::new(0., 0.),
+            FVec2::new(0., 0.),
+            0.05,
+        )
+        .unwrap();
+
+        assert!((time_of_impact - 0.44).abs() < 1e-4);
+        assert_eq!(
+            circle_time_of_impact(
+                FVec2::new(-0.5, 0.),
+                FVec2::new(-1., 0.),
+                0.01,
+                FVec2::new(0., 0.),
+                FVec2::new(0., 0.),
+                0.05,
+            ),
+            None
+        );
+    }
+
+    #[test]
+    fn check_wall_time_of_impact() {
+        let time_of_impact =
+            wall_time_of_impact(FVec2::new(0.5, 0.), FVec2::new(1., 0.), 0.1).unwrap();
+
+        assert!((time_of_impact - 0.4).abs() < 1e-4);
+        assert_eq!(
+            wall_time_of_impact(FVec2::new(0.5, 0.), FVec2::new(0.1, 0.), 0.1),
+            None
+        );
+    }
+
+    #[test]
+    fn check_bullet_slides_along_wall() {
+        // NOTE: Touches the +y wall with gravity pressing it in, so every sweep hits the wall at once.
+        let mut scene = cpu_scene(vec![RigidBody::from(Circle {
+            radius: 0.1,
+            position: FVec2::new(-0.5, 0.9),
+            velocity: FVec2::new(1., 0.),
+            bullet: true,
+            ..Default::default()
+        })]);
+        let steps = 100;
+        for _ in 0..steps {
+            scene.step();
+        }
+
+        let body = &scene.bodies()[0];
+        let expected_x = -0.5 + steps as f32 * scene.get_dt();
+        assert!((body.get_position().x - expected_x).abs() < 1e-3);
+        assert!(body.get_position().y <= 0.9 + 1e-4);
+        assert!(body.get_velocity().y.abs() < 0.1);
+    }
+
+    #[test]
+    fn check_swept_contact_raises_begin_event() {
+        // NOTE: Crosses the other body within one step, so they never overlap at the end of a step.
+        let mut scene = cpu_scene(vec![
+            RigidBody::from(Circle {
+                radius: 0.05,
+                position: FVec2::new(-0.5, 0.),
+                velocity: FVec2::new(1. / DELTA_TIME, 0.),
+                bullet: true,
+                ..Default::default()
+            }),
+            moving_circle(0., 0.),
+        ]);
+        let ids: Vec<_> = scene.bodies().iter().map(RigidBody::get_id).collect();
+        scene.step();
+
+        let events = scene.get_collision_events();
+        assert_eq!(events.len(), 1);
+        assert_eq!(events[0].body_a, ids[0]);
+        assert_eq!(events[0].body_b, ContactTarget::Body(ids[1]));
+        assert_eq!(events[0].phase, CollisionPhase::Begin);
+        assert!(events[0].impulse > 0.);
+        assert!(scene.bodies()[0].get_velocity().x < 0.);
+    }
+}
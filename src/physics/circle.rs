@@ -1,19 +1,34 @@
 use ecolor::Color32;
+use serde::{Deserialize, Serialize};
 
 use crate::FVec2;
 
 use super::{
-    collision::{Collision, CollisionHandler, CollisionObjectType, WorldCollisionInfo},
-    lib::{COEFF_RESTITUTION, WORLD_BOUNDS},
+    collision::{
+        Collision, CollisionFilter, CollisionHandler, CollisionObjectType, WorldCollisionInfo,
+    },
+    lib::WORLD_BOUNDS,
     rigidbody::GenericObject,
 };
 
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
 pub struct Circle {
     pub radius: f32,
     pub position: FVec2,
     pub velocity: FVec2,
     pub color: Color32,
+    #[serde(default)]
+    pub angle: f32,
+    #[serde(default)]
+    pub angular_velocity: f32,
+    #[doc = "Always sweep this circle for continuous collision detection, however slowly it moves"]
+    #[serde(default)]
+    pub bullet: bool,
+    #[doc = "Report overlaps with other bodies without pushing them apart"]
+    #[serde(default)]
+    pub sensor: bool,
+    #[serde(default)]
+    pub filter: CollisionFilter,
 }
 
 impl GenericObject for Circle {
@@ -38,6 +53,9 @@ impl GenericObject for Circle {
 
 impl CollisionHandler for Circle {
     fn check_world_collisions(&self) -> Option<Collision> {
+        if !self.filter.collides_with_world() {
+            return None;
+        }
         let y_pos_range = self.position.y - self.radius..self.position.y + self.radius;
         let x_pos_range = self.position.x - self.radius..self.position.x + self.radius;
         let in_x_bounds = WORLD_BOUNDS.0.contains(&x_pos_range.start)
@@ -53,26 +71,4 @@ impl CollisionHandler for Circle {
 
         None
     }
-    fn resolve_world_collision(&mut self, in_boundaries_xy: WorldCollisionInfo) {
-        let mut distance_to_offset = FVec2::new(0., 0.);
-        let position = self.get_position();
-        if !in_boundaries_xy.get_crossed_x() {
-            self.velocity.x *= -1. * COEFF_RESTITUTION;
-            if position.x + self.get_radius() > WORLD_BOUNDS.0.end {
-                distance_to_offset.x = WORLD_BOUNDS.0.end - position.x - self.get_radius();
-            } else if position.x + self.get_radius() < WORLD_BOUNDS.0.start {
-                distance_to_offset.x = -WORLD_BOUNDS.0.start - position.x + self.get_radius();
-            }
-        }
-        if !in_boundaries_xy.get_crossed_y() {
-            self.velocity.y *= -1. * COEFF_RESTITUTION;
-            if position.y + self.get_radius() > WORLD_BOUNDS.1.end {
-                distance_to_offset.y = WORLD_BOUNDS.1.end - position.y - self.get_radius();
-            } else if position.x + self.get_radius() > WORLD_BOUNDS.1.end {
-                distance_to_offset.y = -WORLD_BOUNDS.1.start - position.y + self.get_radius();
-            }
-        }
-
-        self.position += distance_to_offset * 2.;
-    }
 }
@@ -1,9 +1,12 @@
-use libm::{cos, sin};
-use tracing::info;
+use std::collections::HashSet;
 
-use crate::FVec2;
+use serde::{Deserialize, Serialize};
 
-use super::rigidbody::RigidBody;
+use super::{
+    lib::{DEFAULT_COLLISION_CATEGORY, WORLD_COLLISION_CATEGORY},
+    rigidbody::{RBid, RigidBody},
+    sensor::Sensor,
+};
 
 #[derive(Debug)]
 pub struct Collision {
@@ -16,9 +19,10 @@ pub struct Collision {
 pub enum CollisionObjectType {
     World(WorldCollisionInfo),
     Object,
+    #[doc = "The primary overlaps a sensor body or a trigger. Only reported, never resolved."]
+    Sensor(Sensor),
 }
 
-type ObjectPosVelUpdates = [[FVec2; 2]; 2];
 impl Collision {
     pub fn new(
         collision_type: CollisionObjectType,
@@ -40,76 +44,6 @@ impl Collision {
     pub fn get_secondary(&self) -> Option<RigidBody> {
         self.secondary.clone()
     }
-    #[doc = "Returns the updates as [[position, velocity]] for each object in the collision, marking the reference as 'primary'"]
-    pub fn resolve_objects_and_return_updates(&self) -> ObjectPosVelUpdates {
-        // NOTE: Only solving the circle case
-        // NOTE: Both objects should also exist. Otherwise invalid construction
-        let primary = self.primary.clone().unwrap();
-        let secondary = self.secondary.clone().unwrap();
-        let vector_between_com = primary.get_position() - secondary.get_position();
-        let unit_axis_of_seperation = vector_between_com.get_orthogonal_unit();
-        // NOTE: Uses https://en.wikipedia.org/wiki/Elastic_collision#Two-dimensional_collision_with_two_moving_objects
-        let updated_primary_velocity = primary.get_velocity()
-            - (primary.get_position() - secondary.get_position()).scale(
-                (primary.get_velocity() - secondary.get_velocity())
-                    .dot(primary.get_position() - secondary.get_position())
-                    / vector_between_com.magnitude().powf(2.),
-            );
-        let updated_secondary_velocity = secondary.get_velocity()
-            - (secondary.get_position() - primary.get_position()).scale(
-                (secondary.get_velocity() - primary.get_velocity())
-                    .dot(secondary.get_position() - primary.get_position())
-                    / vector_between_com.magnitude().powf(2.),
-            );
-
-        // Check that the conservation of momentum still holds
-        assert!(
-            (primary.get_velocity() + secondary.get_velocity()
-                - updated_primary_velocity
-                - updated_secondary_velocity)
-                .magnitude()
-                < 0.01
-        );
-
-        let distance_to_move_coms =
-            primary.get_radius() + secondary.get_radius() - vector_between_com.magnitude();
-        let primary_velocity_polar_angle = primary.get_velocity().get_polar_angle();
-        let secondary_velocity_polar_angle = primary.get_velocity().get_polar_angle();
-        let distance_to_move_primary = FVec2::new(
-            distance_to_move_coms * cos(primary_velocity_polar_angle as f64) as f32,
-            distance_to_move_coms * sin(primary_velocity_polar_angle as f64) as f32,
-        ) * 2.;
-        let distance_to_move_secondary = FVec2::new(
-            distance_to_move_coms * cos(secondary_velocity_polar_angle as f64) as f32,
-            distance_to_move_coms * sin(secondary_velocity_polar_angle as f64) as f32,
-        ) * 2.;
-        let updated_primary_position = primary.get_position() + distance_to_move_primary;
-        let updated_secondary_position = secondary.get_position() + distance_to_move_secondary;
-
-        info!(
-            "changed position and velocity of id={} circle with r={} from p={}->{}, v={}->{}",
-            primary.get_id(),
-            primary.get_radius(),
-            primary.get_position(),
-            updated_primary_position,
-            primary.get_velocity(),
-            updated_primary_velocity
-        );
-        info!(
-            "changed position and velocity of id={} circle with r={} from p={}->{}, v={}->{}",
-            secondary.get_id(),
-            secondary.get_radius(),
-            secondary.get_position(),
-            updated_secondary_position,
-            secondary.get_velocity(),
-            updated_secondary_velocity
-        );
-
-        [
-            [updated_primary_position, updated_primary_velocity],
-            [updated_secondary_position, updated_secondary_velocity],
-        ]
-    }
 }
 
 impl PartialEq for Collision {
@@ -161,7 +95,145 @@ impl WorldCollisionInfo {
     }
 }
 
+#[doc = "Collision layers of a body. Two bodies only collide when each one's category is in the other's mask."]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct CollisionFilter {
+    pub category: u32,
+    pub mask: u32,
+}
+
+impl Default for CollisionFilter {
+    fn default() -> Self {
+        Self {
+            category: DEFAULT_COLLISION_CATEGORY,
+            mask: u32::MAX,
+        }
+    }
+}
+
+impl CollisionFilter {
+    pub fn accepts(&self, other: &CollisionFilter) -> bool {
+        self.category & other.mask != 0 && other.category & self.mask != 0
+    }
+    pub fn collides_with_world(&self) -> bool {
+        self.mask & WORLD_COLLISION_CATEGORY != 0
+    }
+}
+
+#[doc = "Decides which pairs of bodies may collide, from their `CollisionFilter`s and a list of pairs that never collide"]
+#[derive(Clone, Debug, Default)]
+pub struct PairFilter {
+    never_collide: HashSet<(RBid, RBid)>,
+}
+
+impl PairFilter {
+    pub fn new(never_collide: Vec<(RBid, RBid)>) -> Self {
+        let mut pair_filter = Self::default();
+        for (body_a, body_b) in never_collide {
+            pair_filter.add_never_collide(body_a, body_b);
+        }
+        pair_filter
+    }
+    pub fn add_never_collide(&mut self, body_a: RBid, body_b: RBid) {
+        self.never_collide
+            .insert((body_a.min(body_b), body_a.max(body_b)));
+    }
+    pub fn remove_body(&mut self, id: RBid) {
+        self.never_collide
+            .retain(|(body_a, body_b)| *body_a != id && *body_b != id);
+    }
+    pub fn get_never_collide(&self) -> Vec<(RBid, RBid)> {
+        self.never_collide.iter().copied().collect()
+    }
+    pub fn should_collide(&self, body_a: &RigidBody, body_b: &RigidBody) -> bool {
+        let (id_a, id_b) = (body_a.get_id(), body_b.get_id());
+        body_a
+            .get_collision_filter()
+            .accepts(&body_b.get_collision_filter())
+            && !self
+                .never_collide
+                .contains(&(id_a.min(id_b), id_a.max(id_b)))
+    }
+}
+
 pub trait CollisionHandler {
     fn check_world_collisions(&self) -> Option<Collision>;
-    fn resolve_world_collision(&mut self, crossed_boundaries_xy: WorldCollisionInfo);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CollisionFilter, PairFilter};
+    use crate::{
+        physics::{lib::WORLD_COLLISION_CATEGORY, test_util::circle},
+        FVec2,
+    };
+
+    fn filter(category: u32, mask: u32) -> CollisionFilter {
+        CollisionFilter { category, mask }
+    }
+
+    #[test]
+    fn check_filter_accepts() {
+        let cases = [
+            (CollisionFilter::default(), CollisionFilter::default(), true),
+            (filter(0b01, 0b10), filter(0b10, 0b01), true),
+            // Each side has to accept the other.
+            (filter(0b01, 0b10), filter(0b10, 0b10), false),
+            (filter(0b01, u32::MAX), filter(0b10, 0b10), false),
+            (filter(0b01, 0), CollisionFilter::default(), false),
+            (filter(0, u32::MAX), CollisionFilter::default(), false),
+        ];
+        for (filter_a, filter_b, expected) in cases {
+            assert_eq!(
+                filter_a.accepts(&filter_b),
+                expected,
+                "{filter_a:?} {filter_b:?}"
+            );
+            assert_eq!(
+                filter_b.accepts(&filter_a),
+                expected,
+                "{filter_b:?} {filter_a:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn check_filter_collides_with_world() {
+        let cases = [
+            (CollisionFilter::default(), true),
+            (filter(0b01, WORLD_COLLISION_CATEGORY), true),
+            (filter(0b01, !WORLD_COLLISION_CATEGORY), false),
+            (filter(0b01, 0), false),
+        ];
+        for (filter, expected) in cases {
+            assert_eq!(filter.collides_with_world(), expected, "{filter:?}");
+        }
+    }
+
+    #[test]
+    fn check_pair_filter_should_collide() {
+        let zero = FVec2::new(0., 0.);
+        let body_a = circle(zero, zero, 0);
+        let body_b = circle(zero, zero, 1);
+        let mut body_c = circle(zero, zero, 2);
+        body_c.set_collision_filter(filter(0b10, 0b10));
+
+        let pair_filter = PairFilter::default();
+        assert!(pair_filter.should_collide(&body_a, &body_b));
+        assert!(!pair_filter.should_collide(&body_a, &body_c));
+        assert!(!pair_filter.should_collide(&body_c, &body_b));
+
+        // Never-collide pairs match in either order, however they were added.
+        for pair_filter in [
+            PairFilter::new(vec![(body_a.get_id(), body_b.get_id())]),
+            PairFilter::new(vec![(body_b.get_id(), body_a.get_id())]),
+        ] {
+            assert!(!pair_filter.should_collide(&body_a, &body_b));
+            assert!(!pair_filter.should_collide(&body_b, &body_a));
+        }
+
+        let mut pair_filter = PairFilter::new(vec![(body_a.get_id(), body_b.get_id())]);
+        pair_filter.remove_body(body_b.get_id());
+        assert!(pair_filter.should_collide(&body_a, &body_b));
+    }
 }
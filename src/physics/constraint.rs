@@ -0,0 +1,437 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+use vulkano::buffer::BufferContents;
+
+use crate::FVec2;
+
+use super::{
+    rigidbody::{RBid, RigidBody},
+    solver::{PositionCorrection, PositionCorrectionInfo},
+};
+
+#[doc = "What the other end of a constraint is attached to. World anchors are fixed points, e.g. the pivot of a pendulum."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Anchor {
+    Body(RBid),
+    World(FVec2),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum ConstraintKind {
+    #[doc = "Keeps the centres exactly `length` apart"]
+    Distance { length: f32 },
+    #[doc = "Damped spring pulling the centres towards `rest_length`"]
+    Spring {
+        rest_length: f32,
+        stiffness: f32,
+        damping: f32,
+    },
+    #[doc = "Keeps the centres at most `max_length` apart, but lets them come closer"]
+    Rope { max_length: f32 },
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Constraint {
+    pub body: RBid,
+    pub anchor: Anchor,
+    pub kind: ConstraintKind,
+}
+
+impl Constraint {
+    pub fn get_kind_id(&self) -> u32 {
+        match self.kind {
+            ConstraintKind::Distance { .. } => 0,
+            ConstraintKind::Spring { .. } => 1,
+            ConstraintKind::Rope { .. } => 2,
+        }
+    }
+}
+
+#[doc = "Layout of a constraint in the compute shader's constraint buffer. `body_b` is -1 for world anchors."]
+#[derive(Clone, Copy, Debug, PartialEq, BufferContents)]
+#[repr(C)]
+pub struct GpuConstraint {
+    pub kind: u32,
+    pub body_a: u32,
+    pub body_b: i32,
+    pub params: [f32; 3],
+    pub anchor: [f32; 2],
+}
+
+#[doc = "State of one end of a constraint during a solve"]
+#[derive(Clone, Copy)]
+struct AnchorState {
+    idx: Option<usize>,
+    position: FVec2,
+    velocity: FVec2,
+    inverse_mass: f32,
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct ConstraintSet {
+    constraints: Vec<Constraint>,
+    accumulated_impulses: Vec<f32>,
+}
+
+impl ConstraintSet {
+    pub fn new(constraints: Vec<Constraint>) -> Self {
+        let accumulated_impulses = vec![0.; constraints.len()];
+        Self {
+            constraints,
+            accumulated_impulses,
+        }
+    }
+    #[doc = "Adds a constraint and returns its index"]
+    pub fn add(&mut self, constraint: Constraint) -> usize {
+        self.constraints.push(constraint);
+        self.accumulated_impulses.push(0.);
+        self.constraints.len() - 1
+    }
+    pub fn get_constraints(&self) -> &[Constraint] {
+        &self.constraints
+    }
+    pub fn is_empty(&self) -> bool {
+        self.constraints.is_empty()
+    }
+    #[doc = "Drops every constraint attached to `id`. Constraints added after them move down to fill the gaps."]
+    pub fn remove_body(&mut self, id: RBid) {
+        let is_attached = |constraint: &Constraint| {
+            constraint.body == id || constraint.anchor == Anchor::Body(id)
+        };
+        let (constraints, accumulated_impulses) = std::iter::zip(
+            self.constraints.drain(..),
+            self.accumulated_impulses.drain(..),
+        )
+        .filter(|(constraint, _)| !is_attached(constraint))
+        .unzip();
+        self.constraints = constraints;
+        self.accumulated_impulses = accumulated_impulses;
+    }
+
+    #[doc = "Applies the spring and damping forces of every spring as a velocity change over `dt`"]
+    pub fn apply_spring_forces(&self, objects: &mut [RigidBody], dt: f32) {
+        let indices = index_objects(objects);
+        for constraint in &self.constraints {
+            let ConstraintKind::Spring {
+                rest_length,
+                stiffness,
+                damping,
+            } = constraint.kind
+            else {
+                continue;
+            };
+            let Some((body, anchor)) = resolve(objects, &indices, constraint) else {
+                continue;
+            };
+            let Some((normal, distance)) = get_axis(body.position, anchor.position) else {
+                continue;
+            };
+            let relative_speed = (anchor.velocity - body.velocity).dot(normal);
+            let force = normal * (stiffness * (distance - rest_length) + damping * relative_speed);
+            apply_impulse(objects, &body, &anchor, force * dt * -1.);
+        }
+    }
+
+    #[doc = "Sequential-impulse pass over the distance and rope constraints. Accumulated impulses are warm started from the previous step."]
+    pub fn solve_velocities(&mut self, objects: &mut [RigidBody], iterations: u32) {
+        let indices = index_objects(objects);
+        for (constraint, accumulated) in
+            std::iter::zip(&self.constraints, self.accumulated_impulses.iter_mut())
+        {
+            let Some((body, anchor)) = resolve(objects, &indices, constraint) else {
+                *accumulated = 0.;
+                continue;
+            };
+            let is_slack = match constraint.kind {
+                ConstraintKind::Rope { max_length } => {
+                    (anchor.position - body.position).magnitude() < max_length
+                }
+                ConstraintKind::Spring { .. } => true,
+                ConstraintKind::Distance { .. } => false,
+            };
+            match get_axis(body.position, anchor.position) {
+                Some((normal, _)) if !is_slack => {
+                    apply_impulse(objects, &body, &anchor, normal * *accumulated)
+                }
+                _ => *accumulated = 0.,
+            }
+        }
+
+        for _ in 0..iterations {
+            for (constraint, accumulated) in
+                std::iter::zip(&self.constraints, self.accumulated_impulses.iter_mut())
+            {
+                if let ConstraintKind::Spring { .. } = constraint.kind {
+                    continue;
+                }
+                let Some((body, anchor)) = resolve(objects, &indices, constraint) else {
+                    continue;
+                };
+                let Some((normal, distance)) = get_axis(body.position, anchor.position) else {
+                    continue;
+                };
+                let inverse_mass_sum = body.inverse_mass + anchor.inverse_mass;
+                if inverse_mass_sum <= 0. {
+                    continue;
+                }
+                let relative_speed = (anchor.velocity - body.velocity).dot(normal);
+                let impulse_change = -relative_speed / inverse_mass_sum;
+                let updated_accumulated = match constraint.kind {
+                    ConstraintKind::Rope { max_length } if distance < max_length => 0.,
+                    // Ropes can only pull the ends together.
+                    ConstraintKind::Rope { .. } => (*accumulated + impulse_change).min(0.),
+                    _ => *accumulated + impulse_change,
+                };
+                let impulse_change = updated_accumulated - *accumulated;
+                *accumulated = updated_accumulated;
+                apply_impulse(objects, &body, &anchor, normal * impulse_change);
+            }
+        }
+    }
+
+    #[doc = "Projects the ends of distance and rope constraints back to their allowed lengths"]
+    pub fn correct_positions(
+        &self,
+        objects: &mut [RigidBody],
+        position_correction: &PositionCorrectionInfo,
+    ) {
+        if position_correction.method == PositionCorrection::None {
+            return;
+        }
+        let indices = index_objects(objects);
+        for _ in 0..position_correction.iterations {
+            for constraint in &self.constraints {
+                let Some((body, anchor)) = resolve(objects, &indices, constraint) else {
+                    continue;
+                };
+                let Some((normal, distance)) = get_axis(body.position, anchor.position) else {
+                    continue;
+                };
+                let error = match constraint.kind {
+                    ConstraintKind::Distance { length } => distance - length,
+                    ConstraintKind::Rope { max_length } => (distance - max_length).max(0.),
+                    ConstraintKind::Spring { .. } => continue,
+                };
+                let inverse_mass_sum = body.inverse_mass + anchor.inverse_mass;
+                if inverse_mass_sum <= 0. {
+                    continue;
+                }
+                let correction =
+                    normal * (error * position_correction.correction_factor / inverse_mass_sum);
+                if let Some(idx) = body.idx {
+                    let updated_position = body.position + correction * body.inverse_mass;
+                    objects[idx].update_position(updated_position);
+                }
+                if let Some(idx) = anchor.idx {
+                    let updated_position = anchor.position - correction * anchor.inverse_mass;
+                    objects[idx].update_position(updated_position);
+                }
+            }
+        }
+    }
+
+    #[doc = "Converts the constraints into the compute shader layout, with ids replaced by buffer indices"]
+    pub fn to_gpu_constraints(&self, objects: &[RigidBody]) -> Vec<GpuConstraint> {
+        let indices = index_objects(objects);
+        self.constraints
+            .iter()
+            .filter_map(|constraint| {
+                let body_a = *indices.get(&constraint.body)? as u32;
+                let (body_b, anchor) = match constraint.anchor {
+                    Anchor::Body(id) => (*indices.get(&id)? as i32, [0., 0.]),
+                    Anchor::World(position) => (-1, position.as_array()),
+                };
+                let params = match constraint.kind {
+                    ConstraintKind::Distance { length } => [length, 0., 0.],
+                    ConstraintKind::Spring {
+                        rest_length,
+                        stiffness,
+                        damping,
+                    } => [rest_length, stiffness, damping],
+                    ConstraintKind::Rope { max_length } => [max_length, 0., 0.],
+                };
+                Some(GpuConstraint {
+                    kind: constraint.get_kind_id(),
+                    body_a,
+                    body_b,
+                    params,
+                    anchor,
+                })
+            })
+            .collect()
+    }
+
+    #[doc = "End points of each constraint, for drawing"]
+    pub fn get_segments(&self, objects: &[RigidBody]) -> Vec<(FVec2, FVec2)> {
+        let indices = index_objects(objects);
+        self.constraints
+            .iter()
+            .filter_map(|constraint| {
+                let (body, anchor) = resolve(objects, &indices, constraint)?;
+                Some((body.position, anchor.position))
+            })
+            .collect()
+    }
+}
+
+fn index_objects(objects: &[RigidBody]) -> HashMap<RBid, usize> {
+    objects
+        .iter()
+        .enumerate()
+        .map(|(idx, obj)| (obj.get_id(), idx))
+        .collect()
+}
+
+fn body_state(objects: &[RigidBody], idx: usize) -> AnchorState {
+    AnchorState {
+        idx: Some(idx),
+        position: objects[idx].get_position(),
+        velocity: objects[idx].get_velocity(),
+        inverse_mass: objects[idx].get_inverse_mass(),
+    }
+}
+
+fn resolve(
+    objects: &[RigidBody],
+    indices: &HashMap<RBid, usize>,
+    constraint: &Constraint,
+) -> Option<(AnchorState, AnchorState)> {
+    let body = body_state(objects, *indices.get(&constraint.body)?);
+    let anchor = match constraint.anchor {
+        Anchor::Body(id) => body_state(objects, *indices.get(&id)?),
+        Anchor::World(position) => AnchorState {
+            idx: None,
+            position,
+            velocity: FVec2::new(0., 0.),
+            inverse_mass: 0.,
+        },
+    };
+
+    Some((body, anchor))
+}
+
+#[doc = "Unit vector from the body to the anchor and the distance between them, if they do not coincide"]
+fn get_axis(body_position: FVec2, anchor_position: FVec2) -> Option<(FVec2, f32)> {
+    let vector_to_anchor = anchor_position - body_position;
+    let distance = vector_to_anchor.magnitude();
+    if distance <= f32::EPSILON {
+        return None;
+    }
+
+    Some((vector_to_anchor * (1. / distance), distance))
+}
+
+#[doc = "Applies `impulse` to the anchor and its opposite to the body, reading the current velocities"]
+fn apply_impulse(
+    objects: &mut [RigidBody],
+    body: &AnchorState,
+    anchor: &AnchorState,
+    impulse: FVec2,
+) {
+    for (state, sign) in [(body, -1.), (anchor, 1.)] {
+        if let Some(idx) = state.idx {
+            let updated_velocity =
+                objects[idx].get_velocity() + impulse * (sign * state.inverse_mass);
+            objects[idx].update_velocity(updated_velocity);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Anchor, Constraint, ConstraintKind, ConstraintSet};
+    use crate::{
+        physics::{
+            rigidbody::{RBid, RigidBody},
+            solver::{PositionCorrection, PositionCorrectionInfo},
+            test_util::circle,
+        },
+        FVec2,
+    };
+
+    const DT: f32 = 1e-3;
+
+    fn anchored(kind: ConstraintKind) -> ConstraintSet {
+        ConstraintSet::new(vec![Constraint {
+            body: RBid::new(0, 0),
+            anchor: Anchor::World(FVec2::new(0., 0.)),
+            kind,
+        }])
+    }
+
+    fn integrate(objects: &mut [RigidBody]) {
+        for obj in objects.iter_mut() {
+            let updated_position = obj.get_position() + obj.get_velocity() * DT;
+            obj.update_position(updated_position);
+        }
+    }
+
+    #[test]
+    fn check_distance_constraint_holds_length() {
+        let mut constraints = anchored(ConstraintKind::Distance { length: 0.3 });
+        let mut objects = vec![circle(FVec2::new(0.3, 0.), FVec2::new(0., 1.), 0)];
+        for _ in 0..500 {
+            constraints.solve_velocities(&mut objects, 10);
+            integrate(&mut objects);
+            constraints.correct_positions(&mut objects, &PositionCorrectionInfo::default());
+        }
+
+        let distance = objects[0].get_position().magnitude();
+        assert!((distance - 0.3).abs() < 1e-3);
+        // The body swings around the anchor rather than stopping.
+        assert!(objects[0].get_velocity().magnitude() > 0.5);
+    }
+
+    #[test]
+    fn check_rope_only_pulls() {
+        let mut constraints = anchored(ConstraintKind::Rope { max_length: 0.3 });
+        let mut objects = vec![circle(FVec2::new(0.1, 0.), FVec2::new(-1., 0.), 0)];
+        constraints.solve_velocities(&mut objects, 10);
+        assert_eq!(objects[0].get_velocity(), FVec2::new(-1., 0.));
+
+        let mut objects = vec![circle(FVec2::new(0.3, 0.), FVec2::new(1., 0.), 0)];
+        constraints.solve_velocities(&mut objects, 10);
+        assert!(objects[0].get_velocity().x.abs() < 1e-5);
+
+        // A stretched rope is pulled back, never pushed out.
+        let mut objects = vec![circle(FVec2::new(0.4, 0.), FVec2::new(0., 0.), 0)];
+        let position_correction = PositionCorrectionInfo::default();
+        constraints.correct_positions(&mut objects, &position_correction);
+        assert!(objects[0].get_position().x < 0.4);
+        let mut objects = vec![circle(FVec2::new(0.2, 0.), FVec2::new(0., 0.), 0)];
+        constraints.correct_positions(&mut objects, &position_correction);
+        assert_eq!(objects[0].get_position(), FVec2::new(0.2, 0.));
+    }
+
+    #[test]
+    fn check_spring_converges_to_rest_length() {
+        let constraints = anchored(ConstraintKind::Spring {
+            rest_length: 0.2,
+            stiffness: 50.,
+            damping: 5.,
+        });
+        let mut objects = vec![circle(FVec2::new(0.5, 0.), FVec2::new(0., 0.), 0)];
+        for _ in 0..5000 {
+            constraints.apply_spring_forces(&mut objects, DT);
+            integrate(&mut objects);
+        }
+
+        assert!((objects[0].get_position().magnitude() - 0.2).abs() < 1e-2);
+        assert!(objects[0].get_velocity().magnitude() < 1e-2);
+    }
+
+    #[test]
+    fn check_no_position_correction_leaves_positions() {
+        let constraints = anchored(ConstraintKind::Distance { length: 0.3 });
+        let mut objects = vec![circle(FVec2::new(0.4, 0.), FVec2::new(0., 0.), 0)];
+        constraints.correct_positions(
+            &mut objects,
+            &PositionCorrectionInfo {
+                method: PositionCorrection::None,
+                ..Default::default()
+            },
+        );
+        assert_eq!(objects[0].get_position(), FVec2::new(0.4, 0.));
+    }
+}
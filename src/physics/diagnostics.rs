@@ -0,0 +1,193 @@
+use std::collections::VecDeque;
+
+use tracing::warn;
+
+use crate::FVec2;
+
+use super::{lib::MAX_DIAGNOSTICS_HISTORY, rigidbody::RigidBody};
+
+#[doc = "Conserved quantities of a scene at one step. Potential energy is measured from y = 0, with gravity pointing along +y."]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct DiagnosticsSample {
+    pub step: u64,
+    pub kinetic_energy: f32,
+    pub potential_energy: f32,
+    pub linear_momentum: FVec2,
+    #[doc = "About the origin, including each body's spin"]
+    pub angular_momentum: f32,
+    pub center_of_mass: FVec2,
+}
+
+impl DiagnosticsSample {
+    pub fn measure(objects: &[RigidBody], gravity: f32, step: u64) -> Self {
+        let mut sample = DiagnosticsSample {
+            step,
+            ..Default::default()
+        };
+        let mut total_mass = 0.;
+        let mut weighted_position = FVec2::default();
+        for body in objects {
+            let mass = body.get_mass();
+            let position = body.get_position();
+            let velocity = body.get_velocity();
+            let angular_velocity = body.get_angular_velocity();
+            sample.kinetic_energy += 0.5 * mass * velocity.dot(velocity)
+                + 0.5 * body.get_inertia() * angular_velocity * angular_velocity;
+            sample.potential_energy -= mass * gravity * position.y;
+            sample.linear_momentum += velocity * mass;
+            sample.angular_momentum +=
+                mass * position.cross(velocity) + body.get_inertia() * angular_velocity;
+            total_mass += mass;
+            weighted_position += position * mass;
+        }
+        if total_mass > 0. {
+            sample.center_of_mass = weighted_position * (1. / total_mass);
+        }
+        sample
+    }
+    pub fn get_total_energy(&self) -> f32 {
+        self.kinetic_energy + self.potential_energy
+    }
+}
+
+#[doc = "Change of each conserved quantity since the first sample. Energy drift is relative to the initial total energy; momentum drifts are absolute."]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Drift {
+    pub energy: f32,
+    pub linear_momentum: f32,
+    pub angular_momentum: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct DiagnosticsInfo {
+    #[doc = "Take a sample every this many steps. `None` only samples on demand."]
+    pub interval: Option<u32>,
+    #[doc = "Warn once the energy drift goes over this"]
+    pub energy_drift_threshold: Option<f32>,
+    #[doc = "Warn once the linear momentum drift goes over this. Walls and gravity change momentum, so this is off by default."]
+    pub linear_momentum_drift_threshold: Option<f32>,
+    pub angular_momentum_drift_threshold: Option<f32>,
+}
+
+impl Default for DiagnosticsInfo {
+    fn default() -> Self {
+        Self {
+            interval: None,
+            energy_drift_threshold: Some(0.1),
+            linear_momentum_drift_threshold: None,
+            angular_momentum_drift_threshold: None,
+        }
+    }
+}
+
+#[doc = "Samples conserved quantities and tracks how far they drift from the start of the simulation"]
+#[derive(Clone, Debug)]
+pub struct Diagnostics {
+    info: DiagnosticsInfo,
+    initial: DiagnosticsSample,
+    history: VecDeque<DiagnosticsSample>,
+    over_threshold: [bool; 3],
+}
+
+impl Diagnostics {
+    pub fn new(info: DiagnosticsInfo, initial: DiagnosticsSample) -> Self {
+        Self {
+            info,
+            initial,
+            history: VecDeque::from([initial]),
+            over_threshold: [false; 3],
+        }
+    }
+    pub fn get_info(&self) -> DiagnosticsInfo {
+        self.info
+    }
+    pub fn set_info(&mut self, info: DiagnosticsInfo) {
+        self.info = info;
+    }
+    pub fn get_initial(&self) -> DiagnosticsSample {
+        self.initial
+    }
+    pub fn get_latest(&self) -> DiagnosticsSample {
+        self.history.back().copied().unwrap_or(self.initial)
+    }
+    #[doc = "Samples taken so far, oldest first. Only the last `MAX_DIAGNOSTICS_HISTORY` are kept."]
+    pub fn get_history(&self) -> &VecDeque<DiagnosticsSample> {
+        &self.history
+    }
+    pub fn get_drift(&self, sample: &DiagnosticsSample) -> Drift {
+        let initial_energy = self.initial.get_total_energy();
+        Drift {
+            energy: (sample.get_total_energy() - initial_energy).abs()
+                / initial_energy.abs().max(f32::EPSILON),
+            linear_momentum: (sample.linear_momentum - self.initial.linear_momentum).magnitude(),
+            angular_momentum: (sample.angular_momentum - self.initial.angular_momentum).abs(),
+        }
+    }
+    #[doc = "Whether a sample is due after `step`, given the configured interval"]
+    pub fn is_due(&self, step: u64) -> bool {
+        self.info
+            .interval
+            .is_some_and(|interval| interval > 0 && step.is_multiple_of(interval as u64))
+    }
+
+    #[doc = "Stores a sample and warns when a drift first goes over its threshold"]
+    pub fn record(&mut self, sample: DiagnosticsSample) {
+        let drift = self.get_drift(&sample);
+        let checks = [
+            ("energy", drift.energy, self.info.energy_drift_threshold),
+            (
+                "linear momentum",
+                drift.linear_momentum,
+                self.info.linear_momentum_drift_threshold,
+            ),
+            (
+                "angular momentum",
+                drift.angular_momentum,
+                self.info.angular_momentum_drift_threshold,
+            ),
+        ];
+        for (idx, (quantity, value, threshold)) in checks.into_iter().enumerate() {
+            let is_over = threshold.is_some_and(|threshold| value > threshold);
+            if is_over && !self.over_threshold[idx] {
+                warn!(
+                    "{} drifted by {} at step {}, over the threshold of {}",
+                    quantity,
+                    value,
+                    sample.step,
+                    threshold.unwrap_or_default()
+                );
+            }
+            self.over_threshold[idx] = is_over;
+        }
+
+        if self.history.len() >= MAX_DIAGNOSTICS_HISTORY {
+            self.history.pop_front();
+        }
+        self.history.push_back(sample);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Diagnostics, DiagnosticsInfo, DiagnosticsSample};
+    use crate::{physics::test_util::circle, FVec2};
+
+    #[test]
+    fn check_sample_and_drift() {
+        let body =
+            |x: f32, vx: f32, index: u32| circle(FVec2::new(x, 0.5), FVec2::new(vx, 0.), index);
+        let objects = [body(-0.5, 1., 0), body(0.5, -1., 1)];
+        let sample = DiagnosticsSample::measure(&objects, 10., 0);
+
+        assert!(sample.linear_momentum.magnitude() < 1e-6);
+        assert!(sample.center_of_mass.x.abs() < 1e-6);
+        assert!((sample.center_of_mass.y - 0.5).abs() < 1e-6);
+        assert!((sample.kinetic_energy - objects[0].get_mass()).abs() < 1e-6);
+
+        let mut diagnostics = Diagnostics::new(DiagnosticsInfo::default(), sample);
+        let faster = [body(-0.5, 2., 0), body(0.5, -2., 1)];
+        diagnostics.record(DiagnosticsSample::measure(&faster, 10., 1));
+        assert!(diagnostics.get_drift(&diagnostics.get_latest()).energy > 0.1);
+        assert_eq!(diagnostics.get_history().len(), 2);
+    }
+}
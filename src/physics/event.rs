@@ -0,0 +1,204 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+use vulkano::buffer::BufferContents;
+
+use crate::FVec2;
+
+use super::{
+    ccd::{velocity_after_impact, Impact},
+    rigidbody::{RBid, RigidBody},
+    solver::{ContactManifold, ContactTarget, Wall},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum CollisionPhase {
+    #[doc = "The bodies started touching this step"]
+    Begin,
+    #[doc = "The bodies were already touching in the previous step"]
+    Persist,
+    #[doc = "The bodies stopped touching this step. Point and normal are from the last step they touched."]
+    End,
+}
+
+#[doc = "Record of a contact during one step. The normal points from `body_a` to `body_b`, and the impulse is the total normal impulse applied along it."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct CollisionEvent {
+    pub body_a: RBid,
+    pub body_b: ContactTarget,
+    pub point: FVec2,
+    pub normal: FVec2,
+    pub impulse: f32,
+    pub phase: CollisionPhase,
+}
+
+impl CollisionEvent {
+    pub fn from_manifold(manifold: &ContactManifold) -> Self {
+        let points = manifold.get_points();
+        let point = points
+            .iter()
+            .fold(FVec2::default(), |sum, point| sum + point.position)
+            * (1. / points.len().max(1) as f32);
+        Self {
+            body_a: manifold.get_primary(),
+            body_b: manifold.get_target(),
+            point,
+            normal: manifold.get_normal(),
+            impulse: points.iter().map(|point| point.get_normal_impulse()).sum(),
+            phase: CollisionPhase::Begin,
+        }
+    }
+    #[doc = "Converts the first contact of the swept body `objects[idx]`, found by `ccd::earliest_impact`. As for discrete contacts, the body with the lower index is `body_a`."]
+    pub fn from_impact(objects: &[RigidBody], idx: usize, dt: f32, impact: &Impact) -> Self {
+        let body = &objects[idx];
+        let point = body.get_position()
+            + body.get_velocity() * (dt * impact.time)
+            + impact.normal * body.get_radius();
+        let velocity_change = body.get_velocity() - velocity_after_impact(objects, idx, impact);
+        let (body_a, body_b, normal) = match impact.other {
+            Some(other) if other < idx => (
+                objects[other].get_id(),
+                ContactTarget::Body(body.get_id()),
+                impact.normal * -1.,
+            ),
+            Some(other) => (
+                body.get_id(),
+                ContactTarget::Body(objects[other].get_id()),
+                impact.normal,
+            ),
+            None => (
+                body.get_id(),
+                ContactTarget::World(Wall::from_normal(impact.normal)),
+                impact.normal,
+            ),
+        };
+        Self {
+            body_a,
+            body_b,
+            point,
+            normal,
+            impulse: body.get_mass() * velocity_change.dot(impact.normal),
+            phase: CollisionPhase::Begin,
+        }
+    }
+    fn get_key(&self) -> (RBid, ContactTarget) {
+        (self.body_a, self.body_b)
+    }
+}
+
+pub type CollisionCallback = Box<dyn FnMut(&CollisionEvent)>;
+
+#[doc = "Matches `CollisionEvent` in `update_cs`. `body_a` and non-negative `body_b` are object indices; a negative `body_b` is the wall `-1 - body_b`, in `Wall` declaration order."]
+#[derive(Clone, Copy, Debug, Default, BufferContents)]
+#[repr(C)]
+pub struct GpuCollisionEvent {
+    pub body_a: u32,
+    pub body_b: i32,
+    pub point: [f32; 2],
+    pub normal: [f32; 2],
+    pub impulse: f32,
+    // NOTE: std430 rounds the struct up to the alignment of its vec2 members.
+    pub _padding: f32,
+}
+
+impl GpuCollisionEvent {
+    #[doc = "Converts to a `CollisionEvent`, translating object indices into `RBid`s. The phase is left to `CollisionEventTracker`."]
+    pub fn to_collision_event(&self, objects: &[RigidBody]) -> Option<CollisionEvent> {
+        let body_a = objects.get(self.body_a as usize)?.get_id();
+        let body_b = match self.body_b {
+            idx if idx >= 0 => ContactTarget::Body(objects.get(idx as usize)?.get_id()),
+            -1 => ContactTarget::World(Wall::NegativeX),
+            -2 => ContactTarget::World(Wall::PositiveX),
+            -3 => ContactTarget::World(Wall::NegativeY),
+            -4 => ContactTarget::World(Wall::PositiveY),
+            _ => return None,
+        };
+        Some(CollisionEvent {
+            body_a,
+            body_b,
+            point: FVec2::from(&self.point),
+            normal: FVec2::from(&self.normal),
+            impulse: self.impulse,
+            phase: CollisionPhase::Begin,
+        })
+    }
+}
+
+#[doc = "Turns the contacts found in each step into begin/persist/end events by comparing them with the previous step"]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct CollisionEventTracker {
+    touching: Vec<CollisionEvent>,
+    events: Vec<CollisionEvent>,
+}
+
+impl CollisionEventTracker {
+    #[doc = "Replaces the pending events with those for `contacts`, the contacts of the step that just ran. A pair reported more than once, e.g. by both ends of a swept contact, keeps its first contact."]
+    pub fn record(&mut self, contacts: Vec<CollisionEvent>) {
+        let previous_keys: HashSet<(RBid, ContactTarget)> =
+            self.touching.iter().map(CollisionEvent::get_key).collect();
+        let mut current_keys: HashSet<(RBid, ContactTarget)> = HashSet::new();
+        let contacts: Vec<CollisionEvent> = contacts
+            .into_iter()
+            .filter(|contact| current_keys.insert(contact.get_key()))
+            .collect();
+
+        self.events.clear();
+        for contact in &contacts {
+            self.events.push(CollisionEvent {
+                phase: match previous_keys.contains(&contact.get_key()) {
+                    true => CollisionPhase::Persist,
+                    false => CollisionPhase::Begin,
+                },
+                ..*contact
+            });
+        }
+        for contact in &self.touching {
+            if !current_keys.contains(&contact.get_key()) {
+                self.events.push(CollisionEvent {
+                    impulse: 0.,
+                    phase: CollisionPhase::End,
+                    ..*contact
+                });
+            }
+        }
+        self.touching = contacts;
+    }
+    pub fn get_events(&self) -> &[CollisionEvent] {
+        &self.events
+    }
+    pub fn drain(&mut self) -> Vec<CollisionEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{CollisionEvent, CollisionEventTracker, CollisionPhase};
+    use crate::{
+        physics::{rigidbody::RBid, solver::ContactTarget},
+        FVec2,
+    };
+
+    #[test]
+    fn check_begin_persist_end() {
+        let contact = CollisionEvent {
+            body_a: RBid::new(0, 0),
+            body_b: ContactTarget::Body(RBid::new(1, 0)),
+            point: FVec2::new(0., 0.),
+            normal: FVec2::new(1., 0.),
+            impulse: 0.5,
+            phase: CollisionPhase::Begin,
+        };
+        let mut tracker = CollisionEventTracker::default();
+
+        tracker.record(vec![contact]);
+        assert_eq!(tracker.get_events()[0].phase, CollisionPhase::Begin);
+        tracker.record(vec![contact]);
+        assert_eq!(tracker.get_events()[0].phase, CollisionPhase::Persist);
+        tracker.record(vec![]);
+        assert_eq!(tracker.get_events()[0].phase, CollisionPhase::End);
+        assert_eq!(tracker.get_events()[0].impulse, 0.);
+        assert_eq!(tracker.drain().len(), 1);
+        assert!(tracker.get_events().is_empty());
+    }
+}
@@ -0,0 +1,755 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Serialize};
+
+use crate::FVec2;
+
+use super::{
+    constraint::Anchor,
+    rigidbody::{RBid, RigidBody},
+    solver::{PositionCorrection, PositionCorrectionInfo},
+};
+
+#[doc = "Drives the relative motion of a joint at `speed`, using at most `max_force` (a torque for revolute joints). For a world anchor, `speed` is that of the body itself, e.g. positive spins it counterclockwise."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Motor {
+    pub speed: f32,
+    pub max_force: f32,
+}
+
+#[doc = "Allowed range of a joint's relative angle (revolute) or translation along its axis (prismatic)"]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JointLimits {
+    pub lower: f32,
+    pub upper: f32,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum JointKind {
+    #[doc = "Pins the anchors together but lets the bodies rotate relative to each other"]
+    Revolute {
+        limits: Option<JointLimits>,
+        motor: Option<Motor>,
+    },
+    #[doc = "Pins the anchors together and locks the relative angle"]
+    Weld,
+    #[doc = "Locks the relative angle and only allows the anchors to slide along `axis`, given in the frame of `body_a`"]
+    Prismatic {
+        axis: FVec2,
+        limits: Option<JointLimits>,
+        motor: Option<Motor>,
+    },
+}
+
+#[doc = "Description of a joint. Anchors are offsets from each body's centre in the body's own (unrotated) frame. A world anchor on `body_b` is a fixed point, and its local anchor is relative to that point."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct JointDef {
+    pub body_a: RBid,
+    pub body_b: Anchor,
+    pub local_anchor_a: FVec2,
+    pub local_anchor_b: FVec2,
+    pub kind: JointKind,
+    #[doc = "The joint breaks once its reaction force exceeds this"]
+    pub break_force: Option<f32>,
+    #[doc = "The joint breaks once its reaction torque exceeds this, e.g. a weld bent too hard or a revolute joint pushed against its limit"]
+    #[serde(default)]
+    pub break_torque: Option<f32>,
+}
+
+impl JointDef {
+    #[doc = "Sign that makes relative angles, speeds and translations those of B relative to A, except for world anchors, where they are those of the body relative to the world"]
+    fn get_direction(&self) -> f32 {
+        match self.body_b {
+            Anchor::Body(_) => 1.,
+            Anchor::World(_) => -1.,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct JointHandle(usize);
+
+#[derive(Clone, Debug, Serialize, Deserialize)]
+struct Joint {
+    def: JointDef,
+    reference_angle: f32,
+    linear_impulse: FVec2,
+    angular_impulse: f32,
+    axial_impulse: f32,
+    motor_impulse: f32,
+    broken: bool,
+}
+
+impl Joint {
+    #[doc = "Angular impulse applied to `body_b` during the last step, by the angle lock or limit and a revolute motor"]
+    fn get_angular_impulse(&self) -> f32 {
+        let motor_impulse = match self.def.kind {
+            JointKind::Revolute { .. } => self.motor_impulse,
+            _ => 0.,
+        };
+        (self.angular_impulse + motor_impulse) * self.def.get_direction()
+    }
+}
+
+#[doc = "State of one side of a joint. Bodies have `idx` set; world anchors are static."]
+#[derive(Clone, Copy)]
+struct BodyState {
+    idx: Option<usize>,
+    position: FVec2,
+    angle: f32,
+    velocity: FVec2,
+    angular_velocity: f32,
+    inverse_mass: f32,
+    inverse_inertia: f32,
+}
+
+impl BodyState {
+    fn from_object(objects: &[RigidBody], idx: usize) -> Self {
+        let obj = &objects[idx];
+        Self {
+            idx: Some(idx),
+            position: obj.get_position(),
+            angle: obj.get_angle(),
+            velocity: obj.get_velocity(),
+            angular_velocity: obj.get_angular_velocity(),
+            inverse_mass: obj.get_inverse_mass(),
+            inverse_inertia: obj.get_inverse_inertia(),
+        }
+    }
+    fn from_point(position: FVec2) -> Self {
+        Self {
+            idx: None,
+            position,
+            angle: 0.,
+            velocity: FVec2::new(0., 0.),
+            angular_velocity: 0.,
+            inverse_mass: 0.,
+            inverse_inertia: 0.,
+        }
+    }
+    fn point_velocity(&self, arm: FVec2) -> FVec2 {
+        self.velocity + arm.perpendicular() * self.angular_velocity
+    }
+}
+
+#[doc = "Linear and angular parts of a one-dimensional constraint row, [-linear, -angular_a, linear, angular_b]"]
+#[derive(Clone, Copy)]
+struct Jacobian {
+    linear: FVec2,
+    angular_a: f32,
+    angular_b: f32,
+}
+
+impl Jacobian {
+    fn angular(direction: f32) -> Self {
+        Self {
+            linear: FVec2::new(0., 0.),
+            angular_a: direction,
+            angular_b: direction,
+        }
+    }
+    fn effective_mass(&self, a: &BodyState, b: &BodyState) -> f32 {
+        let inverse = (a.inverse_mass + b.inverse_mass) * self.linear.dot(self.linear)
+            + a.inverse_inertia * self.angular_a * self.angular_a
+            + b.inverse_inertia * self.angular_b * self.angular_b;
+        if inverse > 0. {
+            1. / inverse
+        } else {
+            0.
+        }
+    }
+    fn relative_velocity(&self, a: &BodyState, b: &BodyState) -> f32 {
+        self.linear.dot(b.velocity - a.velocity) + self.angular_b * b.angular_velocity
+            - self.angular_a * a.angular_velocity
+    }
+}
+
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct JointSet {
+    joints: Vec<Joint>,
+}
+
+impl JointSet {
+    pub fn add(&mut self, def: JointDef, objects: &[RigidBody]) -> JointHandle {
+        let indices = index_objects(objects);
+        let reference_angle = match resolve(objects, &indices, &def) {
+            Some((a, b)) => b.angle - a.angle,
+            None => 0.,
+        };
+        self.joints.push(Joint {
+            def,
+            reference_angle,
+            linear_impulse: FVec2::new(0., 0.),
+            angular_impulse: 0.,
+            axial_impulse: 0.,
+            motor_impulse: 0.,
+            broken: false,
+        });
+        JointHandle(self.joints.len() - 1)
+    }
+    pub fn get_def(&self, handle: JointHandle) -> Option<JointDef> {
+        self.joints.get(handle.0).map(|joint| joint.def)
+    }
+    pub fn is_broken(&self, handle: JointHandle) -> bool {
+        self.joints.get(handle.0).is_some_and(|joint| joint.broken)
+    }
+    pub fn is_empty(&self) -> bool {
+        self.joints.is_empty()
+    }
+    #[doc = "Breaks every joint attached to `id`, so handles to other joints stay valid"]
+    pub fn remove_body(&mut self, id: RBid) {
+        for joint in self.joints.iter_mut() {
+            if joint.def.body_a == id || joint.def.body_b == Anchor::Body(id) {
+                joint.broken = true;
+            }
+        }
+    }
+
+    #[doc = "Force the joint applied to `body_b` during the last step, in world space"]
+    pub fn get_reaction_force(&self, handle: JointHandle, dt: f32) -> Option<FVec2> {
+        let joint = self.joints.get(handle.0)?;
+        Some(joint.linear_impulse * (1. / dt))
+    }
+    #[doc = "Torque the joint applied to `body_b` during the last step"]
+    pub fn get_reaction_torque(&self, handle: JointHandle, dt: f32) -> Option<f32> {
+        let joint = self.joints.get(handle.0)?;
+        Some(joint.get_angular_impulse() / dt)
+    }
+
+    pub fn solve_velocities(&mut self, objects: &mut [RigidBody], dt: f32, iterations: u32) {
+        let indices = index_objects(objects);
+        for joint in self.joints.iter_mut() {
+            joint.linear_impulse = FVec2::new(0., 0.);
+            joint.angular_impulse = 0.;
+            joint.axial_impulse = 0.;
+            joint.motor_impulse = 0.;
+        }
+
+        for _ in 0..iterations {
+            for joint in self.joints.iter_mut().filter(|joint| !joint.broken) {
+                let Some((a, b)) = resolve(objects, &indices, &joint.def) else {
+                    continue;
+                };
+                let direction = joint.def.get_direction();
+                match joint.def.kind {
+                    JointKind::Revolute { limits, motor } => {
+                        let angle = direction * (b.angle - a.angle - joint.reference_angle);
+                        let def = joint.def;
+                        if let Some(motor) = motor {
+                            let max_impulse = motor.max_force * dt;
+                            solve_row(objects, &indices, &def, |a, b| {
+                                let jacobian = Jacobian::angular(direction);
+                                let impulse = -jacobian.effective_mass(a, b)
+                                    * (jacobian.relative_velocity(a, b) - motor.speed);
+                                let accumulated = (joint.motor_impulse + impulse)
+                                    .clamp(-max_impulse, max_impulse);
+                                let impulse = accumulated - joint.motor_impulse;
+                                joint.motor_impulse = accumulated;
+                                (jacobian, impulse)
+                            });
+                        }
+                        if let Some(limits) = limits {
+                            if angle <= limits.lower || angle >= limits.upper {
+                                solve_row(objects, &indices, &def, |a, b| {
+                                    let jacobian = Jacobian::angular(direction);
+                                    let impulse = -jacobian.effective_mass(a, b)
+                                        * jacobian.relative_velocity(a, b);
+                                    let accumulated = joint.angular_impulse + impulse;
+                                    let accumulated = match angle <= limits.lower {
+                                        true => accumulated.max(0.),
+                                        false => accumulated.min(0.),
+                                    };
+                                    let impulse = accumulated - joint.angular_impulse;
+                                    joint.angular_impulse = accumulated;
+                                    (jacobian, impulse)
+                                });
+                            }
+                        }
+                        solve_point(objects, &indices, joint);
+                    }
+                    JointKind::Weld => {
+                        let def = joint.def;
+                        solve_row(objects, &indices, &def, |a, b| {
+                            let jacobian = Jacobian::angular(direction);
+                            let impulse =
+                                -jacobian.effective_mass(a, b) * jacobian.relative_velocity(a, b);
+                            joint.angular_impulse += impulse;
+                            (jacobian, impulse)
+                        });
+                        solve_point(objects, &indices, joint);
+                    }
+                    JointKind::Prismatic {
+                        axis,
+                        limits,
+                        motor,
+                    } => {
+                        let axis = axis * direction;
+                        let def = joint.def;
+                        solve_row(objects, &indices, &def, |a, b| {
+                            let jacobian = Jacobian::angular(direction);
+                            let impulse =
+                                -jacobian.effective_mass(a, b) * jacobian.relative_velocity(a, b);
+                            joint.angular_impulse += impulse;
+                            (jacobian, impulse)
+                        });
+                        let perpendicular = solve_row(objects, &indices, &def, |a, b| {
+                            let jacobian = prismatic_jacobian(&def, a, b, axis.perpendicular());
+                            let impulse =
+                                -jacobian.effective_mass(a, b) * jacobian.relative_velocity(a, b);
+                            (jacobian, impulse)
+                        });
+                        joint.linear_impulse += perpendicular;
+                        if let Some(motor) = motor {
+                            let max_impulse = motor.max_force * dt;
+                            joint.linear_impulse += solve_row(objects, &indices, &def, |a, b| {
+                                let jacobian = prismatic_jacobian(&def, a, b, axis);
+                                let impulse = -jacobian.effective_mass(a, b)
+                                    * (jacobian.relative_velocity(a, b) - motor.speed);
+                                let accumulated = (joint.motor_impulse + impulse)
+                                    .clamp(-max_impulse, max_impulse);
+                                let impulse = accumulated - joint.motor_impulse;
+                                joint.motor_impulse = accumulated;
+                                (jacobian, impulse)
+                            });
+                        }
+                        if let Some(limits) = limits {
+                            let translation = get_translation(&def, &a, &b, axis);
+                            if translation <= limits.lower || translation >= limits.upper {
+                                joint.linear_impulse +=
+                                    solve_row(objects, &indices, &def, |a, b| {
+                                        let jacobian = prismatic_jacobian(&def, a, b, axis);
+                                        let impulse = -jacobian.effective_mass(a, b)
+                                            * jacobian.relative_velocity(a, b);
+                                        let accumulated = joint.axial_impulse + impulse;
+                                        let accumulated = match translation <= limits.lower {
+                                            true => accumulated.max(0.),
+                                            false => accumulated.min(0.),
+                                        };
+                                        let impulse = accumulated - joint.axial_impulse;
+                                        joint.axial_impulse = accumulated;
+                                        (jacobian, impulse)
+                                    });
+                            }
+                        }
+                    }
+                }
+            }
+        }
+
+        for joint in self.joints.iter_mut().filter(|joint| !joint.broken) {
+            let reaction_force = joint.linear_impulse * (1. / dt);
+            let reaction_torque = joint.get_angular_impulse() / dt;
+            let force_exceeded = joint
+                .def
+                .break_force
+                .is_some_and(|break_force| reaction_force.magnitude() > break_force);
+            let torque_exceeded = joint
+                .def
+                .break_torque
+                .is_some_and(|break_torque| reaction_torque.abs() > break_torque);
+            if force_exceeded || torque_exceeded {
+                joint.broken = true;
+            }
+        }
+    }
+
+    #[doc = "Projects the bodies back onto each joint, removing `correction_factor` of the drift per iteration. Skipped when position correction is off."]
+    pub fn correct_positions(
+        &self,
+        objects: &mut [RigidBody],
+        position_correction: &PositionCorrectionInfo,
+    ) {
+        if position_correction.method == PositionCorrection::None {
+            return;
+        }
+        let correction_factor = position_correction.correction_factor;
+        let indices = index_objects(objects);
+        for _ in 0..position_correction.iterations {
+            for joint in self.joints.iter().filter(|joint| !joint.broken) {
+                let Some((a, b)) = resolve(objects, &indices, &joint.def) else {
+                    continue;
+                };
+                let direction = joint.def.get_direction();
+                let angle = direction * (b.angle - a.angle - joint.reference_angle);
+                let angle_error = match joint.def.kind {
+                    JointKind::Weld | JointKind::Prismatic { .. } => angle,
+                    JointKind::Revolute {
+                        limits: Some(limits),
+                        ..
+                    } => angle - angle.clamp(limits.lower, limits.upper),
+                    JointKind::Revolute { limits: None, .. } => 0.,
+                };
+                if angle_error != 0. {
+                    let jacobian = Jacobian::angular(direction);
+                    let impulse =
+                        -jacobian.effective_mass(&a, &b) * angle_error * correction_factor;
+                    apply_position_impulse(objects, &a, &b, &jacobian, impulse);
+                }
+
+                let Some((a, b)) = resolve(objects, &indices, &joint.def) else {
+                    continue;
+                };
+                match joint.def.kind {
+                    JointKind::Revolute { .. } | JointKind::Weld => {
+                        let arm_a = joint.def.local_anchor_a.rotate(a.angle);
+                        let arm_b = joint.def.local_anchor_b.rotate(b.angle);
+                        let error = (b.position + arm_b) - (a.position + arm_a);
+                        if let Some(impulse) = solve_point_system(&a, &b, arm_a, arm_b, error) {
+                            apply_linear_impulse(
+                                objects,
+                                &a,
+                                &b,
+                                arm_a,
+                                arm_b,
+                                impulse * correction_factor,
+                                true,
+                            );
+                        }
+                    }
+                    JointKind::Prismatic { axis, limits, .. } => {
+                        let axis = axis * direction;
+                        let perpendicular = axis.perpendicular();
+                        let offset = get_offset(&joint.def, &a, &b);
+                        let error = offset.dot(perpendicular.rotate(a.angle).get_unit());
+                        let jacobian = prismatic_jacobian(&joint.def, &a, &b, perpendicular);
+                        let impulse = -jacobian.effective_mass(&a, &b) * error * correction_factor;
+                        apply_position_impulse(objects, &a, &b, &jacobian, impulse);
+
+                        if let Some(limits) = limits {
+                            let translation = get_translation(&joint.def, &a, &b, axis);
+                            let error = translation - translation.clamp(limits.lower, limits.upper);
+                            let jacobian = prismatic_jacobian(&joint.def, &a, &b, axis);
+                            let impulse =
+                                -jacobian.effective_mass(&a, &b) * error * correction_factor;
+                            apply_position_impulse(objects, &a, &b, &jacobian, impulse);
+                        }
+                    }
+                }
+            }
+        }
+    }
+
+    #[doc = "Segments between each unbroken joint's anchors and the bodies' centres, for drawing"]
+    pub fn get_segments(&self, objects: &[RigidBody]) -> Vec<(FVec2, FVec2)> {
+        let indices = index_objects(objects);
+        self.joints
+            .iter()
+            .filter(|joint| !joint.broken)
+            .filter_map(|joint| {
+                let (a, b) = resolve(objects, &indices, &joint.def)?;
+                Some((a.position, b.position))
+            })
+            .collect()
+    }
+}
+
+fn index_objects(objects: &[RigidBody]) -> HashMap<RBid, usize> {
+    objects
+        .iter()
+        .enumerate()
+        .map(|(idx, obj)| (obj.get_id(), idx))
+        .collect()
+}
+
+fn resolve(
+    objects: &[RigidBody],
+    indices: &HashMap<RBid, usize>,
+    def: &JointDef,
+) -> Option<(BodyState, BodyState)> {
+    let a = BodyState::from_object(objects, *indices.get(&def.body_a)?);
+    let b = match def.body_b {
+        Anchor::Body(id) => BodyState::from_object(objects, *indices.get(&id)?),
+        Anchor::World(position) => BodyState::from_point(position),
+    };
+
+    Some((a, b))
+}
+
+#[doc = "Vector from anchor A to anchor B in world space"]
+fn get_offset(def: &JointDef, a: &BodyState, b: &BodyState) -> FVec2 {
+    (b.position + def.local_anchor_b.rotate(b.angle))
+        - (a.position + def.local_anchor_a.rotate(a.angle))
+}
+
+fn get_translation(def: &JointDef, a: &BodyState, b: &BodyState, axis: FVec2) -> f32 {
+    get_offset(def, a, b).dot(axis.rotate(a.angle).get_unit())
+}
+
+#[doc = "Row for relative motion along `direction`, which is fixed in the frame of body A"]
+fn prismatic_jacobian(def: &JointDef, a: &BodyState, b: &BodyState, direction: FVec2) -> Jacobian {
+    let direction = direction.rotate(a.angle).get_unit();
+    let arm_a = def.local_anchor_a.rotate(a.angle);
+    let arm_b = def.local_anchor_b.rotate(b.angle);
+    let offset = get_offset(def, a, b);
+    Jacobian {
+        linear: direction,
+        angular_a: (offset + arm_a).cross(direction),
+        angular_b: arm_b.cross(direction),
+    }
+}
+
+#[doc = "Solves one constraint row. `row` returns the jacobian and the impulse to apply; the linear impulse given to body B is returned."]
+fn solve_row(
+    objects: &mut [RigidBody],
+    indices: &HashMap<RBid, usize>,
+    def: &JointDef,
+    row: impl FnOnce(&BodyState, &BodyState) -> (Jacobian, f32),
+) -> FVec2 {
+    let Some((a, b)) = resolve(objects, indices, def) else {
+        return FVec2::new(0., 0.);
+    };
+    let (jacobian, impulse) = row(&a, &b);
+    apply_velocity_impulse(objects, &a, &b, &jacobian, impulse);
+    jacobian.linear * impulse
+}
+
+fn solve_point(objects: &mut [RigidBody], indices: &HashMap<RBid, usize>, joint: &mut Joint) {
+    let Some((a, b)) = resolve(objects, indices, &joint.def) else {
+        return;
+    };
+    let arm_a = joint.def.local_anchor_a.rotate(a.angle);
+    let arm_b = joint.def.local_anchor_b.rotate(b.angle);
+    let relative_velocity = b.point_velocity(arm_b) - a.point_velocity(arm_a);
+    if let Some(impulse) = solve_point_system(&a, &b, arm_a, arm_b, relative_velocity) {
+        joint.linear_impulse += impulse;
+        apply_linear_impulse(objects, &a, &b, arm_a, arm_b, impulse, false);
+    }
+}
+
+#[doc = "Solves K * impulse = -rhs for the 2x2 point-to-point effective mass matrix K"]
+fn solve_point_system(
+    a: &BodyState,
+    b: &BodyState,
+    arm_a: FVec2,
+    arm_b: FVec2,
+    rhs: FVec2,
+) -> Option<FVec2> {
+    let inverse_mass_sum = a.inverse_mass + b.inverse_mass;
+    let k11 = inverse_mass_sum
+        + a.inverse_inertia * arm_a.y * arm_a.y
+        + b.inverse_inertia * arm_b.y * arm_b.y;
+    let k12 = -a.inverse_inertia * arm_a.x * arm_a.y - b.inverse_inertia * arm_b.x * arm_b.y;
+    let k22 = inverse_mass_sum
+        + a.inverse_inertia * arm_a.x * arm_a.x
+        + b.inverse_inertia * arm_b.x * arm_b.x;
+    let determinant = k11 * k22 - k12 * k12;
+    if determinant.abs() <= f32::EPSILON {
+        return None;
+    }
+
+    Some(FVec2::new(
+        -(k22 * rhs.x - k12 * rhs.y) / determinant,
+        -(k11 * rhs.y - k12 * rhs.x) / determinant,
+    ))
+}
+
+fn apply_linear_impulse(
+    objects: &mut [RigidBody],
+    a: &BodyState,
+    b: &BodyState,
+    arm_a: FVec2,
+    arm_b: FVec2,
+    impulse: FVec2,
+    to_positions: bool,
+) {
+    for (state, arm, sign) in [(a, arm_a, -1.), (b, arm_b, 1.)] {
+        let Some(idx) = state.idx else {
+            continue;
+        };
+        let linear = impulse * (sign * state.inverse_mass);
+        let angular = sign * state.inverse_inertia * arm.cross(impulse);
+        apply_delta(&mut objects[idx], linear, angular, to_positions);
+    }
+}
+
+fn apply_velocity_impulse(
+    objects: &mut [RigidBody],
+    a: &BodyState,
+    b: &BodyState,
+    jacobian: &Jacobian,
+    impulse: f32,
+) {
+    apply_row(objects, a, b, jacobian, impulse, false);
+}
+
+fn apply_position_impulse(
+    objects: &mut [RigidBody],
+    a: &BodyState,
+    b: &BodyState,
+    jacobian: &Jacobian,
+    impulse: f32,
+) {
+    apply_row(objects, a, b, jacobian, impulse, true);
+}
+
+fn apply_row(
+    objects: &mut [RigidBody],
+    a: &BodyState,
+    b: &BodyState,
+    jacobian: &Jacobian,
+    impulse: f32,
+    to_positions: bool,
+) {
+    for (state, angular, sign) in [(a, jacobian.angular_a, -1.), (b, jacobian.angular_b, 1.)] {
+        let Some(idx) = state.idx else {
+            continue;
+        };
+        let linear = jacobian.linear * (sign * state.inverse_mass * impulse);
+        let angular = sign * state.inverse_inertia * angular * impulse;
+        apply_delta(&mut objects[idx], linear, angular, to_positions);
+    }
+}
+
+#[doc = "Adds to either the velocities or, for position correction, the position and angle of a body"]
+fn apply_delta(obj: &mut RigidBody, linear: FVec2, angular: f32, to_positions: bool) {
+    match to_positions {
+        true => {
+            obj.update_position(obj.get_position() + linear);
+            obj.update_angle(obj.get_angle() + angular);
+        }
+        false => {
+            obj.update_velocity(obj.get_velocity() + linear);
+            obj.update_angular_velocity(obj.get_angular_velocity() + angular);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{JointDef, JointKind, JointLimits, JointSet, Motor};
+    use crate::{
+        physics::{
+            constraint::Anchor,
+            rigidbody::RBid,
+            solver::{PositionCorrection, PositionCorrectionInfo},
+            test_util::circle,
+        },
+        FVec2,
+    };
+
+    #[test]
+    fn check_weld_moves_bodies_together() {
+        let mut objects = vec![
+            circle(FVec2::new(0., 0.), FVec2::new(1., 0.), 0),
+            circle(FVec2::new(0.2, 0.), FVec2::new(0., 0.), 1),
+        ];
+        let mut joints = JointSet::default();
+        let handle = joints.add(
+            JointDef {
+                body_a: RBid::new(0, 0),
+                body_b: Anchor::Body(RBid::new(1, 0)),
+                local_anchor_a: FVec2::new(0.1, 0.),
+                local_anchor_b: FVec2::new(-0.1, 0.),
+                kind: JointKind::Weld,
+                break_force: None,
+                break_torque: None,
+            },
+            &objects,
+        );
+        joints.solve_velocities(&mut objects, 1e-3, 10);
+
+        let relative_velocity = objects[1].get_velocity() - objects[0].get_velocity();
+        assert!(relative_velocity.magnitude() < 1e-4);
+        assert!(joints.get_reaction_force(handle, 1e-3).unwrap().x > 0.);
+    }
+
+    #[test]
+    fn check_revolute_motor_and_break() {
+        let mut objects = vec![circle(FVec2::new(0., 0.), FVec2::new(0., 0.), 0)];
+        let mut joints = JointSet::default();
+        let motor = joints.add(
+            JointDef {
+                body_a: RBid::new(0, 0),
+                body_b: Anchor::World(FVec2::new(0., 0.)),
+                local_anchor_a: FVec2::new(0., 0.),
+                local_anchor_b: FVec2::new(0., 0.),
+                kind: JointKind::Revolute {
+                    limits: Some(JointLimits {
+                        lower: -10.,
+                        upper: 10.,
+                    }),
+                    motor: Some(Motor {
+                        speed: 2.,
+                        max_force: 1e3,
+                    }),
+                },
+                break_force: Some(1.),
+                break_torque: None,
+            },
+            &objects,
+        );
+        joints.solve_velocities(&mut objects, 1e-3, 10);
+        assert!((objects[0].get_angular_velocity() - 2.).abs() < 1e-3);
+        assert!(!joints.is_broken(motor));
+
+        objects[0].update_velocity(FVec2::new(10., 0.));
+        joints.solve_velocities(&mut objects, 1e-3, 10);
+        assert!(joints.is_broken(motor));
+    }
+
+    #[test]
+    fn check_weld_breaks_under_torque() {
+        let mut objects = vec![
+            circle(FVec2::new(0., 0.), FVec2::new(0., 0.), 0),
+            circle(FVec2::new(0.2, 0.), FVec2::new(0., 0.), 1),
+        ];
+        let mut joints = JointSet::default();
+        let weld = joints.add(
+            JointDef {
+                body_a: RBid::new(0, 0),
+                body_b: Anchor::Body(RBid::new(1, 0)),
+                local_anchor_a: FVec2::new(0.1, 0.),
+                local_anchor_b: FVec2::new(-0.1, 0.),
+                kind: JointKind::Weld,
+                break_force: None,
+                break_torque: Some(1.),
+            },
+            &objects,
+        );
+        // NOTE: Counter-spinning in place needs only torque to stop, so the linear reaction stays zero.
+        objects[0].update_angular_velocity(1e-3);
+        objects[1].update_angular_velocity(-1e-3);
+        joints.solve_velocities(&mut objects, 1e-3, 10);
+        assert!(!joints.is_broken(weld));
+
+        objects[0].update_angular_velocity(50.);
+        objects[1].update_angular_velocity(-50.);
+        joints.solve_velocities(&mut objects, 1e-3, 10);
+        assert!(joints.get_reaction_force(weld, 1e-3).unwrap().magnitude() < 1e-3);
+        assert!(joints.is_broken(weld));
+    }
+
+    #[test]
+    fn check_no_position_correction_leaves_positions() {
+        let mut objects = vec![
+            circle(FVec2::new(0., 0.), FVec2::new(0., 0.), 0),
+            circle(FVec2::new(0.2, 0.), FVec2::new(0., 0.), 1),
+        ];
+        let mut joints = JointSet::default();
+        joints.add(
+            JointDef {
+                body_a: RBid::new(0, 0),
+                body_b: Anchor::Body(RBid::new(1, 0)),
+                local_anchor_a: FVec2::new(0.1, 0.),
+                local_anchor_b: FVec2::new(-0.1, 0.),
+                kind: JointKind::Weld,
+                break_force: None,
+                break_torque: None,
+            },
+            &objects,
+        );
+        objects[1].update_position(FVec2::new(0.3, 0.1));
+        objects[1].update_angle(0.5);
+        joints.correct_positions(
+            &mut objects,
+            &PositionCorrectionInfo {
+                method: PositionCorrection::None,
+                ..Default::default()
+            },
+        );
+
+        assert_eq!(objects[0].get_position(), FVec2::new(0., 0.));
+        assert_eq!(objects[1].get_position(), FVec2::new(0.3, 0.1));
+        assert_eq!(objects[1].get_angle(), 0.5);
+    }
+}
@@ -5,3 +5,39 @@ pub const DELTA_TIME: f32 = 1e-3;
 pub const GRAVITY_ACCELERATION: f32 = 30.;
 pub const WORLD_BOUNDS: (Range<f32>, Range<f32>) = ((-1 as f32..1 as f32), (-1 as f32..1 as f32));
 pub const COEFF_RESTITUTION: f32 = 0.8;
+#[doc = "Approach speed below which contacts are treated as resting and do not bounce"]
+pub const RESTITUTION_VELOCITY_THRESHOLD: f32 = 0.05;
+#[doc = "Penetration depth that is tolerated without correction, so resting contacts stay in contact"]
+pub const PENETRATION_SLOP: f32 = 0.005;
+#[doc = "Fraction of the penetration beyond the slop that is removed each step"]
+pub const POSITION_CORRECTION_FACTOR: f32 = 0.4;
+#[doc = "Width, in world units, of the lines drawn for constraints"]
+pub const CONSTRAINT_LINE_WIDTH: f32 = 0.008;
+#[doc = "Fraction of its radius a body may move in one step before it is swept for continuous collision detection"]
+pub const CCD_MOTION_THRESHOLD: f32 = 0.5;
+#[doc = "Collision category bodies belong to unless told otherwise"]
+pub const DEFAULT_COLLISION_CATEGORY: u32 = 1;
+#[doc = "Category of the walls at `WORLD_BOUNDS`. Bodies whose mask leaves this bit out pass through the walls."]
+pub const WORLD_COLLISION_CATEGORY: u32 = 1 << 31;
+#[doc = "Number of diagnostics samples kept for plotting; older ones are dropped"]
+pub const MAX_DIAGNOSTICS_HISTORY: usize = 10_000;
+#[doc = "Capacity of the GPU collision event buffer. Events past this in a single step are dropped."]
+pub const MAX_GPU_COLLISION_EVENTS: usize = 1024;
+#[doc = "Speed above which a body is taken to have blown up"]
+pub const MAX_BODY_SPEED: f32 = 100.;
+#[doc = "How far past `WORLD_BOUNDS` a body may sit before it counts as escaped. Wall contacts allow a little penetration."]
+pub const SANITY_BOUNDS_MARGIN: f32 = 0.1;
+#[doc = "Number of steps kept for the dump written when a sanity check fails"]
+pub const SANITY_HISTORY_LENGTH: usize = 120;
+#[doc = "Encoding version of `SceneSnapshot`. Bump it whenever the snapshotted state changes shape."]
+pub const SNAPSHOT_VERSION: u32 = 2;
+#[doc = "Encoding version of `ReplayRecording`"]
+pub const REPLAY_VERSION: u32 = 2;
+#[doc = "Steps between the timeline keyframes used to rewind a scene"]
+pub const TIMELINE_KEYFRAME_INTERVAL: u32 = 20;
+#[doc = "Bytes of encoded keyframes the timeline keeps before dropping the oldest"]
+pub const TIMELINE_MEMORY_BUDGET: usize = 64 * 1024 * 1024;
+#[doc = "Natural frequency, in hertz, of the spring that drags bodies with the mouse"]
+pub const MOUSE_JOINT_FREQUENCY: f32 = 5.;
+pub const MOUSE_JOINT_DAMPING_RATIO: f32 = 0.7;
+pub const MOUSE_JOINT_MAX_ACCELERATION: f32 = 300.;
@@ -1,5 +1,21 @@
+pub mod arena;
+pub mod ccd;
 pub mod circle;
 pub mod collision;
+pub mod constraint;
+pub mod diagnostics;
+pub mod event;
+pub mod joint;
 pub mod lib;
+pub mod mouse_joint;
+pub mod query;
+pub mod replay;
 pub mod rigidbody;
+pub mod sanity;
 pub mod scene;
+pub mod sensor;
+pub mod snapshot;
+pub mod solver;
+#[cfg(test)]
+pub mod test_util;
+pub mod timeline;
@@ -0,0 +1,94 @@
+use serde::{Deserialize, Serialize};
+
+use crate::FVec2;
+
+use super::{
+    lib::{MOUSE_JOINT_DAMPING_RATIO, MOUSE_JOINT_FREQUENCY, MOUSE_JOINT_MAX_ACCELERATION},
+    rigidbody::{RBid, RigidBody},
+};
+
+#[doc = "Feel of the mouse joint. The spring is scaled by the body's mass, so heavy and light bodies follow the cursor alike."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MouseJointInfo {
+    #[doc = "Natural frequency of the spring, in hertz"]
+    pub frequency: f32,
+    #[doc = "1 is critically damped; lower values overshoot the cursor"]
+    pub damping_ratio: f32,
+    #[doc = "Largest acceleration the joint applies, so a far-away cursor does not launch the body"]
+    pub max_acceleration: f32,
+}
+
+impl Default for MouseJointInfo {
+    fn default() -> Self {
+        Self {
+            frequency: MOUSE_JOINT_FREQUENCY,
+            damping_ratio: MOUSE_JOINT_DAMPING_RATIO,
+            max_acceleration: MOUSE_JOINT_MAX_ACCELERATION,
+        }
+    }
+}
+
+#[doc = "Damped spring pulling the point where a body was grabbed towards `target`. The grab point keeps its offset from the body's centre."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct MouseJoint {
+    pub body: RBid,
+    pub target: FVec2,
+    pub grab_offset: FVec2,
+    pub info: MouseJointInfo,
+}
+
+impl MouseJoint {
+    pub fn new(body: &RigidBody, point: FVec2, info: MouseJointInfo) -> Self {
+        Self {
+            body: body.get_id(),
+            target: point,
+            grab_offset: point - body.get_position(),
+            info,
+        }
+    }
+
+    pub fn get_anchor(&self, body: &RigidBody) -> FVec2 {
+        body.get_position() + self.grab_offset
+    }
+
+    // NOTE: Damping is applied implicitly, so a stiff joint stays stable with a large `dt`.
+    #[doc = "Changes the velocity of `body` by the spring's acceleration over `dt`"]
+    pub fn apply(&self, body: &mut RigidBody, dt: f32) {
+        let omega = 2. * std::f32::consts::PI * self.info.frequency;
+        let stretch = self.target - self.get_anchor(body);
+        let mut acceleration = stretch * (omega * omega);
+        if acceleration.magnitude() > self.info.max_acceleration {
+            acceleration = acceleration.get_unit() * self.info.max_acceleration;
+        }
+        let damping = 1. + dt * 2. * self.info.damping_ratio * omega;
+        let updated_velocity = (body.get_velocity() + acceleration * dt) * (1. / damping);
+        body.update_velocity(updated_velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::{
+        physics::test_util::{cpu_scene, moving_circle},
+        FVec2,
+    };
+
+    use super::MouseJointInfo;
+
+    #[test]
+    fn check_grabbed_body_follows_target() {
+        let mut scene = cpu_scene(vec![moving_circle(-0.5, 0.)]);
+        let id = scene.bodies()[0].get_id();
+
+        scene.grab(id, FVec2::new(-0.45, 0.), MouseJointInfo::default());
+        scene.drag_to(FVec2::new(0.35, -0.3));
+        for _ in 0..2000 {
+            scene.step();
+        }
+        let position = scene.body(id).unwrap().get_position();
+        assert!((position - FVec2::new(0.3, -0.3)).magnitude() < 0.05);
+
+        scene.release();
+        assert!(scene.get_mouse_joint().is_none());
+    }
+}
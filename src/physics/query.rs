@@ -0,0 +1,198 @@
+use crate::FVec2;
+
+use super::rigidbody::{RBid, RigidBody};
+
+#[doc = "Which bodies a spatial query can return. A body is considered when its collision category is in `mask`."]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct QueryFilter {
+    pub mask: u32,
+    pub include_sensors: bool,
+}
+
+impl Default for QueryFilter {
+    fn default() -> Self {
+        Self {
+            mask: u32::MAX,
+            include_sensors: true,
+        }
+    }
+}
+
+impl QueryFilter {
+    pub fn accepts(&self, body: &RigidBody) -> bool {
+        body.get_collision_filter().category & self.mask != 0
+            && (self.include_sensors || !body.is_sensor())
+    }
+}
+
+#[doc = "First body along a ray. The normal points out of the body at `point`; `distance` is measured from the ray origin."]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct RaycastHit {
+    pub body: RBid,
+    pub point: FVec2,
+    pub normal: FVec2,
+    pub distance: f32,
+}
+
+pub fn circle_overlaps_aabb(center: FVec2, radius: f32, min: FVec2, max: FVec2) -> bool {
+    let closest = FVec2::new(center.x.clamp(min.x, max.x), center.y.clamp(min.y, max.y));
+    (center - closest).magnitude() <= radius
+}
+
+#[doc = "Distance along the unit `direction` at which a ray from `origin` enters a circle. Rays starting inside hit at 0."]
+fn ray_circle_distance(origin: FVec2, direction: FVec2, center: FVec2, radius: f32) -> Option<f32> {
+    let offset = origin - center;
+    let b = offset.dot(direction);
+    let c = offset.dot(offset) - radius * radius;
+    if c > 0. && b > 0. {
+        return None;
+    }
+    let discriminant = b * b - c;
+    if discriminant < 0. {
+        return None;
+    }
+    Some((-b - discriminant.sqrt()).max(0.))
+}
+
+// NOTE: There is no broadphase yet, so every query tests each body in turn.
+pub fn raycast(
+    objects: &[RigidBody],
+    origin: FVec2,
+    direction: FVec2,
+    max_length: f32,
+    filter: &QueryFilter,
+) -> Option<RaycastHit> {
+    if direction.magnitude() <= f32::EPSILON {
+        return None;
+    }
+    let direction = direction.get_unit();
+    objects
+        .iter()
+        .filter(|body| filter.accepts(body))
+        .filter_map(|body| {
+            let distance =
+                ray_circle_distance(origin, direction, body.get_position(), body.get_radius())?;
+            if distance > max_length {
+                return None;
+            }
+            let point = origin + direction * distance;
+            let outward = point - body.get_position();
+            let normal = if outward.magnitude() > f32::EPSILON {
+                outward.get_unit()
+            } else {
+                direction * -1.
+            };
+            Some(RaycastHit {
+                body: body.get_id(),
+                point,
+                normal,
+                distance,
+            })
+        })
+        .min_by(|hit, other| hit.distance.total_cmp(&other.distance))
+}
+
+pub fn query_point(objects: &[RigidBody], point: FVec2, filter: &QueryFilter) -> Vec<RBid> {
+    overlap_circle(objects, point, 0., filter)
+}
+
+#[doc = "Bodies whose shape overlaps the axis-aligned box from `min` to `max`"]
+pub fn query_aabb(
+    objects: &[RigidBody],
+    min: FVec2,
+    max: FVec2,
+    filter: &QueryFilter,
+) -> Vec<RBid> {
+    objects
+        .iter()
+        .filter(|body| {
+            filter.accepts(body)
+                && circle_overlaps_aabb(body.get_position(), body.get_radius(), min, max)
+        })
+        .map(|body| body.get_id())
+        .collect()
+}
+
+pub fn overlap_circle(
+    objects: &[RigidBody],
+    center: FVec2,
+    radius: f32,
+    filter: &QueryFilter,
+) -> Vec<RBid> {
+    objects
+        .iter()
+        .filter(|body| {
+            filter.accepts(body)
+                && (body.get_position() - center).magnitude() <= body.get_radius() + radius
+        })
+        .map(|body| body.get_id())
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{query_aabb, query_point, raycast, QueryFilter};
+    use crate::{
+        physics::{
+            collision::CollisionFilter,
+            rigidbody::{RBid, RigidBody},
+            test_util::circle,
+        },
+        FVec2,
+    };
+
+    fn circle_at(x: f32, index: u32, category: u32) -> RigidBody {
+        let mut body = circle(FVec2::new(x, 0.), FVec2::new(0., 0.), index);
+        body.set_collision_filter(CollisionFilter {
+            category,
+            mask: u32::MAX,
+        });
+        body
+    }
+
+    #[test]
+    fn check_raycast_returns_first_hit() {
+        let objects = [circle_at(0.8, 0, 1), circle_at(0.4, 1, 2)];
+        let origin = FVec2::new(0., 0.);
+        let direction = FVec2::new(1., 0.);
+
+        let hit = raycast(&objects, origin, direction, 2., &QueryFilter::default()).unwrap();
+        assert_eq!(hit.body, RBid::new(1, 0));
+        assert!((hit.distance - 0.3).abs() < 1e-5);
+        assert_eq!(hit.normal, FVec2::new(-1., 0.));
+
+        let only_first_layer = QueryFilter {
+            mask: 1,
+            ..Default::default()
+        };
+        assert_eq!(
+            raycast(&objects, origin, direction, 2., &only_first_layer)
+                .unwrap()
+                .body,
+            RBid::new(0, 0)
+        );
+        assert_eq!(
+            raycast(&objects, origin, direction, 0.2, &QueryFilter::default()),
+            None
+        );
+    }
+
+    #[test]
+    fn check_point_and_aabb_queries() {
+        let objects = [circle_at(0.8, 0, 1), circle_at(0.4, 1, 1)];
+
+        assert_eq!(
+            query_point(&objects, FVec2::new(0.45, 0.05), &QueryFilter::default()),
+            vec![RBid::new(1, 0)]
+        );
+        assert_eq!(
+            query_aabb(
+                &objects,
+                FVec2::new(0., -1.),
+                FVec2::new(0.75, 1.),
+                &QueryFilter::default()
+            ),
+            vec![RBid::new(0, 0), RBid::new(1, 0)]
+        );
+    }
+}
@@ -0,0 +1,376 @@
+use std::{collections::BTreeSet, fmt, path::Path};
+
+use anyhow::bail;
+use serde::{Deserialize, Serialize};
+
+use crate::FVec2;
+
+use super::{
+    constraint::Constraint,
+    joint::JointDef,
+    lib::REPLAY_VERSION,
+    mouse_joint::MouseJointInfo,
+    rigidbody::{RBid, RigidBody},
+    sanity::SanityInfo,
+    scene::{Backend, Scene, SceneInfo},
+    sensor::Trigger,
+    snapshot::SceneSnapshot,
+    solver::SolverInfo,
+};
+
+#[doc = "A change made to a scene from outside the simulation. Each one mirrors a `Scene` method."]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub enum ReplayEvent {
+    SetGravity(f32),
+    SetDt(f32),
+    SetSolverInfo(SolverInfo),
+    SetSanityInfo(SanityInfo),
+    Spawn(RigidBody),
+    Despawn(RBid),
+    ApplyImpulse {
+        body: RBid,
+        impulse: FVec2,
+    },
+    SetVelocity {
+        body: RBid,
+        velocity: FVec2,
+    },
+    Teleport {
+        body: RBid,
+        position: FVec2,
+    },
+    #[doc = "State of a body after it was edited through `Scene::body_mut`"]
+    SetBody(RigidBody),
+    AddConstraint(Constraint),
+    AddJoint(JointDef),
+    AddTrigger(Trigger),
+    AddNeverCollide(RBid, RBid),
+    Grab {
+        body: RBid,
+        point: FVec2,
+        info: MouseJointInfo,
+    },
+    DragTo(FVec2),
+    Release,
+    Pause,
+    Resume,
+    Restore(Box<SceneSnapshot>),
+}
+
+impl ReplayEvent {
+    pub fn apply(&self, scene: &mut Scene) {
+        match self {
+            ReplayEvent::SetGravity(gravity) => scene.set_gravity(*gravity),
+            ReplayEvent::SetDt(dt) => scene.set_dt(*dt),
+            ReplayEvent::SetSolverInfo(info) => scene.set_solver_info(*info),
+            ReplayEvent::SetSanityInfo(info) => scene.set_sanity_info(info.clone()),
+            ReplayEvent::Spawn(body) => {
+                scene.spawn(body.clone());
+            }
+            ReplayEvent::Despawn(id) => {
+                scene.despawn(*id);
+            }
+            ReplayEvent::ApplyImpulse { body, impulse } => scene.apply_impulse(*body, *impulse),
+            ReplayEvent::SetVelocity { body, velocity } => scene.set_velocity(*body, *velocity),
+            ReplayEvent::Teleport { body, position } => scene.teleport(*body, *position),
+            ReplayEvent::SetBody(body) => {
+                if let Some(current) = scene.body_mut(body.get_id()) {
+                    *current = body.clone();
+                }
+            }
+            ReplayEvent::AddConstraint(constraint) => {
+                scene.add_constraint(*constraint);
+            }
+            ReplayEvent::AddJoint(joint_def) => {
+                scene.add_joint(*joint_def);
+            }
+            ReplayEvent::AddTrigger(trigger) => {
+                scene.add_trigger(*trigger);
+            }
+            ReplayEvent::AddNeverCollide(body_a, body_b) => {
+                scene.add_never_collide(*body_a, *body_b)
+            }
+            ReplayEvent::Grab { body, point, info } => scene.grab(*body, *point, *info),
+            ReplayEvent::DragTo(point) => scene.drag_to(*point),
+            ReplayEvent::Release => scene.release(),
+            ReplayEvent::Pause => scene.pause(),
+            ReplayEvent::Resume => scene.resume(),
+            ReplayEvent::Restore(snapshot) => scene.restore(snapshot),
+        }
+    }
+}
+
+#[doc = "An event together with the step count at the time it happened, i.e. it is applied before step `step + 1`"]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct TimedReplayEvent {
+    pub step: u64,
+    pub event: ReplayEvent,
+}
+
+#[doc = "FNV-1a hash over the exact bits of every body's handle and motion state. Stable across runs and processes."]
+pub fn state_checksum(objects: &[RigidBody]) -> u64 {
+    let mut hash: u64 = 0xcbf2_9ce4_8422_2325;
+    let mut write = |word: u32| {
+        for byte in word.to_le_bytes() {
+            hash ^= byte as u64;
+            hash = hash.wrapping_mul(0x0000_0100_0000_01b3);
+        }
+    };
+    for body in objects {
+        let id = body.get_id();
+        let position = body.get_position();
+        let velocity = body.get_velocity();
+        write(id.get_index());
+        write(id.get_generation());
+        for value in [
+            position.x,
+            position.y,
+            velocity.x,
+            velocity.y,
+            body.get_angle(),
+            body.get_angular_velocity(),
+        ] {
+            write(value.to_bits());
+        }
+    }
+    hash
+}
+
+#[doc = "A recorded run: the state it started from, every outside change, and the checksum after each step"]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ReplayRecording {
+    version: u32,
+    pub initial: SceneSnapshot,
+    pub sanity: SanityInfo,
+    pub events: Vec<TimedReplayEvent>,
+    #[doc = "`checksums[i]` is taken after step `initial.get_step_count() + i + 1`"]
+    pub checksums: Vec<u64>,
+}
+
+impl ReplayRecording {
+    pub fn new(initial: SceneSnapshot, sanity: SanityInfo) -> Self {
+        Self {
+            version: REPLAY_VERSION,
+            initial,
+            sanity,
+            events: vec![],
+            checksums: vec![],
+        }
+    }
+
+    pub fn to_json(&self) -> anyhow::Result<String> {
+        Ok(serde_json::to_string(self)?)
+    }
+    pub fn from_json(json: &str) -> anyhow::Result<Self> {
+        let recording: Self = serde_json::from_str(json)?;
+        check_version(recording.version)?;
+        Ok(recording)
+    }
+    pub fn to_bytes(&self) -> anyhow::Result<Vec<u8>> {
+        Ok(bincode::serialize(self)?)
+    }
+    pub fn from_bytes(bytes: &[u8]) -> anyhow::Result<Self> {
+        check_version(bincode::deserialize(bytes)?)?;
+        Ok(bincode::deserialize(bytes)?)
+    }
+    #[doc = "Writes JSON if `path` ends in `.json`, and the binary encoding otherwise"]
+    pub fn save(&self, path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let path = path.as_ref();
+        match is_json(path) {
+            true => std::fs::write(path, self.to_json()?)?,
+            false => std::fs::write(path, self.to_bytes()?)?,
+        }
+        Ok(())
+    }
+    pub fn load(path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let path = path.as_ref();
+        match is_json(path) {
+            true => Self::from_json(&std::fs::read_to_string(path)?),
+            false => Self::from_bytes(&std::fs::read(path)?),
+        }
+    }
+}
+
+fn is_json(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension == "json")
+}
+
+fn check_version(version: u32) -> anyhow::Result<()> {
+    if version != REPLAY_VERSION {
+        bail!(
+            "replay version {} is not supported, expected {}",
+            version,
+            REPLAY_VERSION
+        );
+    }
+    Ok(())
+}
+
+#[doc = "Builds a `ReplayRecording` while a scene runs. Edits made through `Scene::body_mut` cannot be seen as they happen, so the edited bodies are stored whole before the next step."]
+#[derive(Clone, Debug)]
+pub struct ReplayRecorder {
+    recording: ReplayRecording,
+    edited_bodies: BTreeSet<RBid>,
+}
+
+impl ReplayRecorder {
+    pub fn new(initial: SceneSnapshot, sanity: SanityInfo) -> Self {
+        Self {
+            recording: ReplayRecording::new(initial, sanity),
+            edited_bodies: BTreeSet::new(),
+        }
+    }
+    pub fn push(&mut self, step: u64, event: ReplayEvent) {
+        self.recording.events.push(TimedReplayEvent { step, event });
+    }
+    pub fn mark_edited(&mut self, id: RBid) {
+        self.edited_bodies.insert(id);
+    }
+    #[doc = "Records the current state of every body edited since the last flush"]
+    pub fn flush_edited(&mut self, step: u64, objects: &[RigidBody]) {
+        for id in std::mem::take(&mut self.edited_bodies) {
+            // NOTE: Bodies despawned after being edited are skipped.
+            if let Some(body) = objects.iter().find(|body| body.get_id() == id) {
+                self.push(step, ReplayEvent::SetBody(body.clone()));
+            }
+        }
+    }
+    pub fn push_checksum(&mut self, objects: &[RigidBody]) {
+        self.recording.checksums.push(state_checksum(objects));
+    }
+    pub fn get_recording(&self) -> &ReplayRecording {
+        &self.recording
+    }
+    pub fn finish(self) -> ReplayRecording {
+        self.recording
+    }
+}
+
+#[doc = "First step at which a replay no longer matches its recording"]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct ReplayDivergence {
+    pub step: u64,
+    pub expected: u64,
+    pub actual: u64,
+}
+
+impl fmt::Display for ReplayDivergence {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "replay diverged at step {}: expected checksum {:016x}, got {:016x}",
+            self.step, self.expected, self.actual
+        )
+    }
+}
+
+impl std::error::Error for ReplayDivergence {}
+
+#[doc = "Re-runs a recording on the CPU backend and compares the state after every step"]
+pub struct ReplayPlayer {
+    scene: Scene,
+    recording: ReplayRecording,
+    next_event: usize,
+    steps_replayed: usize,
+}
+
+impl ReplayPlayer {
+    pub fn new(recording: ReplayRecording) -> Self {
+        let mut scene = Scene::with_info(SceneInfo {
+            backend: Backend::Cpu,
+            sanity: SanityInfo {
+                dump_path: None,
+                ..recording.sanity.clone()
+            },
+            ..Default::default()
+        });
+        scene.restore(&recording.initial);
+        Self {
+            scene,
+            recording,
+            next_event: 0,
+            steps_replayed: 0,
+        }
+    }
+    pub fn get_scene(&self) -> &Scene {
+        &self.scene
+    }
+    pub fn get_steps_replayed(&self) -> usize {
+        self.steps_replayed
+    }
+    pub fn is_finished(&self) -> bool {
+        self.steps_replayed >= self.recording.checksums.len()
+    }
+
+    #[doc = "Applies the events due before the next step, runs it, and checks the result against the recorded checksum"]
+    pub fn step(&mut self) -> Result<(), ReplayDivergence> {
+        // NOTE: The step count is read again after every event, since a restore can move it back.
+        while let Some(timed) = self.recording.events.get(self.next_event) {
+            if timed.step > self.scene.get_step_count() {
+                break;
+            }
+            timed.event.apply(&mut self.scene);
+            self.next_event += 1;
+        }
+        // NOTE: Every checksum is for a step that ran, so pauses in the recording are not waited on.
+        self.scene.step_once();
+
+        let expected = self.recording.checksums[self.steps_replayed];
+        let actual = state_checksum(self.scene.bodies());
+        self.steps_replayed += 1;
+        match expected == actual {
+            true => Ok(()),
+            false => Err(ReplayDivergence {
+                step: self.scene.get_step_count(),
+                expected,
+                actual,
+            }),
+        }
+    }
+
+    #[doc = "Replays every recorded step, stopping at the first divergence"]
+    pub fn run(&mut self) -> Result<(), ReplayDivergence> {
+        while !self.is_finished() {
+            self.step()?;
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ReplayPlayer, ReplayRecording};
+    use crate::{
+        physics::test_util::{cpu_scene, moving_circle},
+        FVec2,
+    };
+
+    #[test]
+    fn check_replay_matches_and_detects_divergence() {
+        let mut scene = cpu_scene(vec![moving_circle(-0.5, 0.), moving_circle(0.5, 0.)]);
+        let first = scene.bodies()[0].get_id();
+        scene.start_recording();
+        for step in 0..200 {
+            if step == 50 {
+                scene.apply_impulse(first, FVec2::new(0.05, 0.));
+                scene.spawn(moving_circle(0., 0.));
+            }
+            if step == 120 {
+                scene.set_gravity(-5.);
+            }
+            scene.step();
+        }
+        let recording = scene.stop_recording().unwrap();
+        let recording = ReplayRecording::from_bytes(&recording.to_bytes().unwrap()).unwrap();
+
+        let mut player = ReplayPlayer::new(recording.clone());
+        assert_eq!(player.run(), Ok(()));
+        assert_eq!(player.get_scene().bodies(), scene.bodies());
+
+        let mut tampered = recording;
+        tampered.events.retain(|timed| timed.step != 120);
+        let divergence = ReplayPlayer::new(tampered).run().unwrap_err();
+        assert_eq!(divergence.step, 121);
+    }
+}
@@ -1,8 +1,10 @@
 use super::circle::Circle;
-use super::collision::{Collision, CollisionHandler, WorldCollisionInfo};
+use super::collision::{Collision, CollisionFilter, CollisionHandler};
 use crate::renderer::vk_procedural_functions::{generate_polygon_triangles, Polygon};
 use crate::FVec2;
 use ecolor::Color32;
+use serde::{Deserialize, Serialize};
+use std::f32::consts::PI;
 
 #[derive(PartialEq, Clone, Copy)]
 pub enum RigidBodySelection {
@@ -32,8 +34,61 @@ pub trait GenericObject {
     fn get_color(&self) -> Color32;
 }
 
-type RBid = u8;
-#[derive(Clone, Debug, PartialEq)]
+#[doc = "Generational handle to a body. `index` names a slot in the scene's `BodyArena`, and `generation` tells apart the bodies that have used that slot, so handles to despawned bodies are detected."]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize)]
+pub struct RBid {
+    index: u32,
+    generation: u32,
+}
+
+#[doc = "Scene files may give a plain index, which is read as the first generation"]
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum RBidRepr {
+    Index(u32),
+    Handle { index: u32, generation: u32 },
+}
+
+#[derive(Deserialize)]
+struct RBidHandle {
+    index: u32,
+    generation: u32,
+}
+
+// NOTE: Untagged enums need a self-describing format, so binary encodings only take the full
+// handle.
+impl<'de> Deserialize<'de> for RBid {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        if !deserializer.is_human_readable() {
+            let RBidHandle { index, generation } = RBidHandle::deserialize(deserializer)?;
+            return Ok(RBid::new(index, generation));
+        }
+        Ok(match RBidRepr::deserialize(deserializer)? {
+            RBidRepr::Index(index) => RBid::new(index, 0),
+            RBidRepr::Handle { index, generation } => RBid::new(index, generation),
+        })
+    }
+}
+
+impl RBid {
+    pub fn new(index: u32, generation: u32) -> Self {
+        Self { index, generation }
+    }
+    pub fn get_index(&self) -> u32 {
+        self.index
+    }
+    pub fn get_generation(&self) -> u32 {
+        self.generation
+    }
+}
+
+impl std::fmt::Display for RBid {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{}v{}", self.index, self.generation)
+    }
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
 pub enum RigidBody {
     Circle_(Circle, RBid),
 }
@@ -41,13 +96,17 @@ pub enum RigidBody {
 impl CollisionHandler for RigidBody {
     fn check_world_collisions(&self) -> Option<Collision> {
         match self {
-            RigidBody::Circle_(c, _) => c.check_world_collisions(),
+            RigidBody::Circle_(c, _) => c.check_world_collisions().map(|collision| {
+                Collision::new(collision.get_collision_type(), Some(self.clone()), None)
+            }),
         }
     }
-    fn resolve_world_collision(&mut self, has_crossed_boundaries: WorldCollisionInfo) {
-        match self {
-            RigidBody::Circle_(c, _) => return c.resolve_world_collision(has_crossed_boundaries),
-        }
+}
+
+#[doc = "Wraps a circle with a placeholder id. The scene assigns the real handle when the body is added."]
+impl From<Circle> for RigidBody {
+    fn from(value: Circle) -> Self {
+        RigidBody::Circle_(value, RBid::default())
     }
 }
 
@@ -55,25 +114,17 @@ impl CollisionHandler for RigidBody {
 impl RigidBody {
     pub fn get_id(&self) -> RBid {
         match self {
-            RigidBody::Circle_(Circle { .. }, id) => id.clone(),
+            RigidBody::Circle_(Circle { .. }, id) => *id,
+        }
+    }
+    pub(super) fn set_id(&mut self, id: RBid) {
+        match self {
+            RigidBody::Circle_(_, rbid) => *rbid = id,
         }
     }
     pub fn get_object(&self) -> impl GenericObject {
         match self {
-            RigidBody::Circle_(
-                Circle {
-                    radius,
-                    position,
-                    velocity,
-                    color,
-                },
-                _,
-            ) => Circle {
-                radius: *radius,
-                position: *position,
-                velocity: *velocity,
-                color: *color,
-            },
+            RigidBody::Circle_(c, _) => c.clone(),
         }
     }
     pub fn get_vertex_count(&self) -> u8 {
@@ -113,6 +164,85 @@ impl RigidBody {
             RigidBody::Circle_(c, _) => c.velocity,
         }
     }
+    pub fn get_angle(&self) -> f32 {
+        match self {
+            RigidBody::Circle_(c, _) => c.angle,
+        }
+    }
+    pub fn get_angular_velocity(&self) -> f32 {
+        match self {
+            RigidBody::Circle_(c, _) => c.angular_velocity,
+        }
+    }
+    #[doc = "Mass from the area of the body at unit density"]
+    pub fn get_mass(&self) -> f32 {
+        match self {
+            RigidBody::Circle_(c, _) => PI * c.radius * c.radius,
+        }
+    }
+    pub fn get_inverse_mass(&self) -> f32 {
+        let mass = self.get_mass();
+        if mass > 0. {
+            1. / mass
+        } else {
+            0.
+        }
+    }
+    #[doc = "Moment of inertia about the centre"]
+    pub fn get_inertia(&self) -> f32 {
+        match self {
+            RigidBody::Circle_(c, _) => 0.5 * self.get_mass() * c.radius * c.radius,
+        }
+    }
+    pub fn get_inverse_inertia(&self) -> f32 {
+        let inertia = self.get_inertia();
+        if inertia > 0. {
+            1. / inertia
+        } else {
+            0.
+        }
+    }
+    pub fn is_bullet(&self) -> bool {
+        match self {
+            RigidBody::Circle_(c, _) => c.bullet,
+        }
+    }
+    pub fn set_bullet(&mut self, bullet: bool) {
+        match self {
+            RigidBody::Circle_(c, _) => c.bullet = bullet,
+        }
+    }
+    pub fn is_sensor(&self) -> bool {
+        match self {
+            RigidBody::Circle_(c, _) => c.sensor,
+        }
+    }
+    pub fn set_sensor(&mut self, sensor: bool) {
+        match self {
+            RigidBody::Circle_(c, _) => c.sensor = sensor,
+        }
+    }
+    pub fn get_collision_filter(&self) -> CollisionFilter {
+        match self {
+            RigidBody::Circle_(c, _) => c.filter,
+        }
+    }
+    pub fn set_collision_filter(&mut self, filter: CollisionFilter) {
+        match self {
+            RigidBody::Circle_(c, _) => c.filter = filter,
+        }
+    }
+    #[doc = "Flags read by `update_cs` from the second component of the radii buffer: 1 for bullets, 2 for sensors"]
+    pub fn get_gpu_flags(&self) -> u32 {
+        self.is_bullet() as u32 | (self.is_sensor() as u32) << 1
+    }
+    pub fn collides_with(&self, other: &RigidBody) -> bool {
+        if self.get_id() == other.get_id() {
+            return false;
+        }
+        let distance_between_coms = (self.get_position() - other.get_position()).magnitude();
+        distance_between_coms <= self.get_radius() + other.get_radius()
+    }
     pub fn update_position(&mut self, position: FVec2) {
         match self {
             RigidBody::Circle_(c, _) => c.position = position,
@@ -123,6 +253,16 @@ impl RigidBody {
             RigidBody::Circle_(c, _) => c.velocity = velocity,
         }
     }
+    pub fn update_angle(&mut self, angle: f32) {
+        match self {
+            RigidBody::Circle_(c, _) => c.angle = angle,
+        }
+    }
+    pub fn update_angular_velocity(&mut self, angular_velocity: f32) {
+        match self {
+            RigidBody::Circle_(c, _) => c.angular_velocity = angular_velocity,
+        }
+    }
     fn get_debug(&self) -> String {
         let inner_object = self.get_object();
         inner_object.get_debug()
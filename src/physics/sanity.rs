@@ -0,0 +1,291 @@
+use std::{collections::VecDeque, fmt, path::PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::FVec2;
+
+use super::{
+    constraint::Constraint,
+    lib::{MAX_BODY_SPEED, SANITY_BOUNDS_MARGIN, SANITY_HISTORY_LENGTH, WORLD_BOUNDS},
+    rigidbody::{RBid, RigidBody},
+    sensor::Trigger,
+};
+
+#[doc = "What the scene does once a step fails a sanity check"]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SanityResponse {
+    #[doc = "Stop stepping until `Scene::resume` is called"]
+    #[default]
+    Pause,
+    #[doc = "Pull the offending bodies back to a valid state and carry on"]
+    Clamp,
+    #[doc = "Panic after writing the dump"]
+    Abort,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SanityInfo {
+    #[doc = "Bodies faster than this are flagged. `None` skips the check."]
+    pub max_speed: Option<f32>,
+    #[doc = "How far past `WORLD_BOUNDS` a body may be before it counts as escaped. `None` skips the check."]
+    pub bounds_margin: Option<f32>,
+    pub response: SanityResponse,
+    #[doc = "Number of passing steps kept for the dump"]
+    pub history_length: usize,
+    #[doc = "Where the dump is written on pause or abort. `None` only logs."]
+    pub dump_path: Option<PathBuf>,
+}
+
+impl Default for SanityInfo {
+    fn default() -> Self {
+        Self {
+            max_speed: Some(MAX_BODY_SPEED),
+            bounds_margin: Some(SANITY_BOUNDS_MARGIN),
+            response: SanityResponse::default(),
+            history_length: SANITY_HISTORY_LENGTH,
+            dump_path: Some(PathBuf::from("sanity_dump.json")),
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize)]
+pub enum SanityViolation {
+    #[doc = "Position, velocity, angle or angular velocity is NaN or infinite"]
+    NonFinite {
+        body: RBid,
+    },
+    Speed {
+        body: RBid,
+        speed: f32,
+    },
+    OutOfBounds {
+        body: RBid,
+        position: FVec2,
+    },
+}
+
+impl SanityViolation {
+    pub fn get_body(&self) -> RBid {
+        match self {
+            SanityViolation::NonFinite { body }
+            | SanityViolation::Speed { body, .. }
+            | SanityViolation::OutOfBounds { body, .. } => *body,
+        }
+    }
+}
+
+impl fmt::Display for SanityViolation {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            SanityViolation::NonFinite { body } => write!(f, "body {} is not finite", body),
+            SanityViolation::Speed { body, speed } => {
+                write!(f, "body {} is moving at {}", body, speed)
+            }
+            SanityViolation::OutOfBounds { body, position } => {
+                write!(f, "body {} escaped to {:?}", body, position)
+            }
+        }
+    }
+}
+
+#[doc = "Bodies at the end of one step"]
+#[derive(Clone, Debug, Serialize)]
+pub struct StepRecord {
+    pub step: u64,
+    pub bodies: Vec<RigidBody>,
+}
+
+#[doc = "Written when a step fails its sanity checks. The top-level fields hold the oldest recorded step in scene file coordinates, so the dump loads with `parse_scene_file` to replay up to the failure. Joints are not included."]
+#[derive(Serialize)]
+pub struct SanityDump<'a> {
+    pub objects: Vec<RigidBody>,
+    pub constraints: &'a [Constraint],
+    pub triggers: &'a [Trigger],
+    pub never_collide: Vec<(RBid, RBid)>,
+    pub dt: f32,
+    pub gravity: f32,
+    pub failed_step: u64,
+    pub violations: &'a [SanityViolation],
+    #[doc = "Bodies after the failing step, in simulation coordinates"]
+    pub failed_bodies: &'a [RigidBody],
+    #[doc = "The last passing steps, oldest first, in simulation coordinates"]
+    pub history: &'a VecDeque<StepRecord>,
+}
+
+fn is_finite(body: &RigidBody) -> bool {
+    let position = body.get_position();
+    let velocity = body.get_velocity();
+    [
+        position.x,
+        position.y,
+        velocity.x,
+        velocity.y,
+        body.get_angle(),
+        body.get_angular_velocity(),
+    ]
+    .iter()
+    .all(|value| value.is_finite())
+}
+
+#[doc = "Checks the bodies after a step and keeps the last few passing steps around for a dump"]
+#[derive(Clone, Debug, Default)]
+pub struct SanityMonitor {
+    info: SanityInfo,
+    history: VecDeque<StepRecord>,
+}
+
+impl SanityMonitor {
+    pub fn new(info: SanityInfo) -> Self {
+        Self {
+            info,
+            history: VecDeque::new(),
+        }
+    }
+    pub fn get_info(&self) -> &SanityInfo {
+        &self.info
+    }
+    pub fn set_info(&mut self, info: SanityInfo) {
+        self.info = info;
+        while self.history.len() > self.info.history_length {
+            self.history.pop_front();
+        }
+    }
+    pub fn get_history(&self) -> &VecDeque<StepRecord> {
+        &self.history
+    }
+    pub fn clear_history(&mut self) {
+        self.history.clear();
+    }
+
+    pub fn record(&mut self, step: u64, objects: &[RigidBody]) {
+        if self.info.history_length == 0 {
+            return;
+        }
+        if self.history.len() >= self.info.history_length {
+            self.history.pop_front();
+        }
+        self.history.push_back(StepRecord {
+            step,
+            bodies: objects.to_vec(),
+        });
+    }
+
+    #[doc = "Every check each body fails. A non-finite body is not checked any further."]
+    pub fn check(&self, objects: &[RigidBody]) -> Vec<SanityViolation> {
+        let mut violations: Vec<SanityViolation> = vec![];
+        for body in objects {
+            let id = body.get_id();
+            if !is_finite(body) {
+                violations.push(SanityViolation::NonFinite { body: id });
+                continue;
+            }
+            let speed = body.get_velocity().magnitude();
+            if self
+                .info
+                .max_speed
+                .is_some_and(|max_speed| speed > max_speed)
+            {
+                violations.push(SanityViolation::Speed { body: id, speed });
+            }
+            // NOTE: Bodies that ignore the walls are allowed to leave.
+            let position = body.get_position();
+            let escaped = self.info.bounds_margin.is_some_and(|margin| {
+                position.x < WORLD_BOUNDS.0.start - margin
+                    || position.x > WORLD_BOUNDS.0.end + margin
+                    || position.y < WORLD_BOUNDS.1.start - margin
+                    || position.y > WORLD_BOUNDS.1.end + margin
+            });
+            if escaped && body.get_collision_filter().collides_with_world() {
+                violations.push(SanityViolation::OutOfBounds { body: id, position });
+            }
+        }
+        violations
+    }
+
+    #[doc = "Resets non-finite bodies to their last recorded state (or to rest at the origin), slows bodies down to the speed limit and moves escaped bodies back inside the walls"]
+    pub fn clamp(&self, objects: &mut [RigidBody], violations: &[SanityViolation]) {
+        for violation in violations {
+            let Some(body) = objects
+                .iter_mut()
+                .find(|body| body.get_id() == violation.get_body())
+            else {
+                continue;
+            };
+            match *violation {
+                SanityViolation::NonFinite { body: id } => {
+                    let last_good = self
+                        .history
+                        .back()
+                        .and_then(|record| record.bodies.iter().find(|body| body.get_id() == id));
+                    match last_good {
+                        Some(last_good) => *body = last_good.clone(),
+                        None => {
+                            body.update_position(FVec2::new(0., 0.));
+                            body.update_velocity(FVec2::new(0., 0.));
+                            body.update_angle(0.);
+                            body.update_angular_velocity(0.);
+                        }
+                    }
+                }
+                SanityViolation::Speed { speed, .. } => {
+                    let max_speed = self.info.max_speed.unwrap_or(speed);
+                    body.update_velocity(body.get_velocity() * (max_speed / speed));
+                }
+                SanityViolation::OutOfBounds { position, .. } => {
+                    let radius = body.get_radius();
+                    let clamped = FVec2::new(
+                        position.x.clamp(
+                            WORLD_BOUNDS.0.start + radius,
+                            (WORLD_BOUNDS.0.end - radius).max(WORLD_BOUNDS.0.start + radius),
+                        ),
+                        position.y.clamp(
+                            WORLD_BOUNDS.1.start + radius,
+                            (WORLD_BOUNDS.1.end - radius).max(WORLD_BOUNDS.1.start + radius),
+                        ),
+                    );
+                    // NOTE: Only the velocity component carrying the body further out is removed.
+                    let mut velocity = body.get_velocity();
+                    if (clamped.x - position.x) * velocity.x < 0. {
+                        velocity.x = 0.;
+                    }
+                    if (clamped.y - position.y) * velocity.y < 0. {
+                        velocity.y = 0.;
+                    }
+                    body.update_position(clamped);
+                    body.update_velocity(velocity);
+                }
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{SanityInfo, SanityMonitor, SanityViolation};
+    use crate::{physics::test_util::circle, FVec2};
+
+    #[test]
+    fn check_violations_and_clamp() {
+        let body = |position: FVec2, velocity: FVec2| circle(position, velocity, 0);
+        let mut monitor = SanityMonitor::new(SanityInfo::default());
+        let good = [body(FVec2::new(0.2, 0.), FVec2::new(1., 0.))];
+        assert!(monitor.check(&good).is_empty());
+        monitor.record(1, &good);
+
+        let mut bad = [body(FVec2::new(f32::NAN, 0.), FVec2::new(1., 0.))];
+        let violations = monitor.check(&bad);
+        assert!(matches!(
+            violations[..],
+            [SanityViolation::NonFinite { .. }]
+        ));
+        monitor.clamp(&mut bad, &violations);
+        assert_eq!(bad[0].get_position(), FVec2::new(0.2, 0.));
+
+        let mut bad = [body(FVec2::new(3., 0.), FVec2::new(1e4, 1.))];
+        let violations = monitor.check(&bad);
+        assert_eq!(violations.len(), 2);
+        monitor.clamp(&mut bad, &violations);
+        assert!(monitor.check(&bad).is_empty());
+        assert_eq!(bad[0].get_velocity().x, 0.);
+    }
+}
@@ -1,8 +1,8 @@
-use std::hash::RandomState;
 use std::{collections::HashMap, sync::Arc};
 
-use tracing::info;
-use vulkano::buffer::{Buffer, Subbuffer};
+use ecolor::Color32;
+use tracing::{error, info, warn};
+use vulkano::buffer::Buffer;
 use vulkano::command_buffer::allocator::StandardCommandBufferAllocator;
 use vulkano::command_buffer::PrimaryAutoCommandBuffer;
 use vulkano::device::Queue;
@@ -20,19 +20,106 @@ use crate::renderer::vk_core::{RuntimeBuffers, VulkanoContext, WindowContext};
 use crate::{
     renderer::{
         vk_core::{CustomVertex, WindowEventHandler},
-        vk_primitives::create_memory_allocator,
-        vk_procedural_functions::{Polygon, PolygonMethods},
+        vk_procedural_functions::{generate_line_triangles, Polygon},
     },
     FVec2,
 };
 
-use super::rigidbody::RigidBody;
+use super::arena::BodyArena;
+use super::ccd::{earliest_impact, needs_ccd, velocity_after_impact, Impact};
+use super::circle::Circle;
+use super::collision::{Collision, CollisionHandler, CollisionObjectType, PairFilter};
+use super::constraint::{Anchor, Constraint, ConstraintSet, GpuConstraint};
+use super::diagnostics::{Diagnostics, DiagnosticsInfo, DiagnosticsSample};
+use super::event::{CollisionCallback, CollisionEvent, CollisionEventTracker, GpuCollisionEvent};
+use super::joint::{JointDef, JointHandle, JointSet};
+use super::lib::{
+    CCD_MOTION_THRESHOLD, CONSTRAINT_LINE_WIDTH, DELTA_TIME, GRAVITY_ACCELERATION,
+    MAX_GPU_COLLISION_EVENTS, SNAPSHOT_VERSION,
+};
+use super::mouse_joint::{MouseJoint, MouseJointInfo};
+use super::query::{self, QueryFilter, RaycastHit};
+use super::replay::{ReplayEvent, ReplayRecorder, ReplayRecording};
+use super::rigidbody::{RBid, RigidBody};
+use super::sanity::{SanityDump, SanityInfo, SanityMonitor, SanityResponse, SanityViolation};
+use super::sensor::{
+    get_sensor_collisions, Sensor, SensorCallback, SensorEvent, SensorTracker, Trigger,
+};
+use super::snapshot::SceneSnapshot;
+use super::solver::{
+    ContactManifold, ContactSolver, PositionCorrection, PositionCorrectionInfo, SolverInfo,
+};
+use super::timeline::{Timeline, TimelineInfo};
 
 pub struct Scene {
-    objects: Vec<RigidBody>,
-    objects_hash: HashMap<u8, (RigidBody, Polygon)>,
+    objects: BodyArena,
+    #[doc = "Polygon of each body, in the same order as `objects`"]
+    polygons: Vec<Polygon>,
     dt: f32,
     gravity: f32,
+    backend: Backend,
+    rendering: Rendering,
+    body_style: BodyStyle,
+    contact_solver: ContactSolver,
+    constraints: ConstraintSet,
+    joints: JointSet,
+    collision_events: CollisionEventTracker,
+    collision_callbacks: Vec<CollisionCallback>,
+    triggers: Vec<Trigger>,
+    pair_filter: PairFilter,
+    sensor_events: SensorTracker,
+    sensor_callbacks: Vec<SensorCallback>,
+    sync_to_gpu: bool,
+    #[doc = "Buffers the GPU has stepped further than `objects`, until `read_back` catches up"]
+    unread_buffers: Option<RuntimeBuffers>,
+    body_set_changed: bool,
+    step_count: u64,
+    diagnostics: Diagnostics,
+    sanity: SanityMonitor,
+    paused: bool,
+    recorder: Option<ReplayRecorder>,
+    timeline: Timeline,
+    #[doc = "Set while `seek` re-simulates steps that are already on the timeline"]
+    seeking: bool,
+    mouse_joint: Option<MouseJoint>,
+}
+
+#[doc = "Where the physics step runs. The CPU backend uses `Scene::step`; the GPU backend dispatches `update_cs`."]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Backend {
+    #[doc = "Contacts go through the sequential-impulse `ContactSolver`, with restitution and warm starting, so stacks settle"]
+    #[default]
+    Cpu,
+    #[doc = "Contacts are resolved by reflecting velocities, with no restitution loss, accumulated impulses or joints. Stacks jitter and sink, and the same scene plays out differently than on the CPU."]
+    Gpu,
+}
+
+#[doc = "Where the window draws bodies from on the GPU backend. The CPU backend always draws them from the CPU."]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Rendering {
+    #[doc = "Bodies are read back after every step and drawn from the CPU"]
+    #[default]
+    Readback,
+    #[doc = "Bodies are drawn straight from the compute shader's buffers. They are read back to the CPU every `readback_interval` steps, for events, sanity checks, diagnostics and the timeline, and whenever the scene is edited."]
+    Resident { readback_interval: u32 },
+}
+
+#[doc = "How the window shades bodies"]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum BodyStyle {
+    #[doc = "Polygons with as many sides as the body's vertex count, with hard edges"]
+    #[default]
+    Polygon,
+    #[doc = "Exact circles with anti-aliased edges at any zoom, drawn as squares whose pixels measure their distance to the edge"]
+    Sdf { outline: Option<Outline> },
+}
+
+#[doc = "Ring drawn just inside the edge of each body"]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Outline {
+    #[doc = "In pixels, so outlines keep their width when zooming"]
+    pub width: f32,
+    pub color: Color32,
 }
 
 #[derive(Clone)]
@@ -40,6 +127,41 @@ pub struct SceneInfo {
     pub objects: Vec<RigidBody>,
     pub dt: f32,
     pub gravity: f32,
+    pub backend: Backend,
+    pub rendering: Rendering,
+    pub body_style: BodyStyle,
+    pub solver: SolverInfo,
+    pub position_correction: PositionCorrectionInfo,
+    pub constraints: Vec<Constraint>,
+    pub triggers: Vec<Trigger>,
+    pub never_collide: Vec<(RBid, RBid)>,
+    pub diagnostics: DiagnosticsInfo,
+    pub sanity: SanityInfo,
+    pub timeline: TimelineInfo,
+    #[doc = "Whether the ids on `objects` come from a scene description. They are then remapped to arena handles wherever constraints and never-collide pairs name them; otherwise those name arena handles directly."]
+    pub described_ids: bool,
+}
+
+impl Default for SceneInfo {
+    fn default() -> Self {
+        Self {
+            objects: vec![],
+            dt: DELTA_TIME,
+            gravity: GRAVITY_ACCELERATION,
+            backend: Backend::default(),
+            rendering: Rendering::default(),
+            body_style: BodyStyle::default(),
+            solver: SolverInfo::default(),
+            position_correction: PositionCorrectionInfo::default(),
+            constraints: vec![],
+            triggers: vec![],
+            never_collide: vec![],
+            diagnostics: DiagnosticsInfo::default(),
+            sanity: SanityInfo::default(),
+            timeline: TimelineInfo::default(),
+            described_ids: false,
+        }
+    }
 }
 
 impl Scene {
@@ -53,34 +175,814 @@ impl Scene {
             obj.update_position(updated_position);
         });
 
-        let polygons: Vec<Polygon> = scene_info
-            .objects
+        // NOTE: The arena hands out fresh handles, so ids from a scene description are remapped
+        // wherever constraints and never-collide pairs refer to them. Bodies built with
+        // `RigidBody::from` carry a placeholder id, so scenes built in code name arena handles.
+        let mut objects = BodyArena::default();
+        let mut remapped_ids: HashMap<RBid, RBid> = HashMap::new();
+        for body in scene_info.objects {
+            let described_id = body.get_id();
+            let id = objects.insert(body);
+            if scene_info.described_ids && remapped_ids.insert(described_id, id).is_some() {
+                warn!("more than one body was described with id={}", described_id);
+            }
+        }
+        let remap = |id: RBid| match scene_info.described_ids {
+            true => remapped_ids.get(&id).copied(),
+            false => objects.contains(id).then_some(id),
+        };
+        let constraints: Vec<Constraint> = scene_info
+            .constraints
+            .into_iter()
+            .filter_map(|constraint| {
+                let body = remap(constraint.body);
+                let anchor = match constraint.anchor {
+                    Anchor::Body(id) => remap(id).map(Anchor::Body),
+                    anchor => Some(anchor),
+                };
+                let (Some(body), Some(anchor)) = (body, anchor) else {
+                    warn!(
+                        "skipping constraint between {} and {:?}: no body has that id",
+                        constraint.body, constraint.anchor
+                    );
+                    return None;
+                };
+                Some(Constraint {
+                    body,
+                    anchor,
+                    ..constraint
+                })
+            })
+            .collect();
+        let never_collide: Vec<(RBid, RBid)> = scene_info
+            .never_collide
+            .into_iter()
+            .filter_map(|(body_a, body_b)| match (remap(body_a), remap(body_b)) {
+                (Some(body_a), Some(body_b)) => Some((body_a, body_b)),
+                _ => {
+                    warn!(
+                        "skipping never-collide pair ({}, {}): no body has that id",
+                        body_a, body_b
+                    );
+                    None
+                }
+            })
+            .collect();
+        let polygons: Vec<Polygon> = objects.iter().map(|body| body.to_polygon()).collect();
+        let diagnostics = Diagnostics::new(
+            scene_info.diagnostics,
+            DiagnosticsSample::measure(&objects, scene_info.gravity, 0),
+        );
+
+        let mut scene = Self {
+            objects,
+            dt: scene_info.dt,
+            polygons,
+            gravity: scene_info.gravity,
+            backend: scene_info.backend,
+            rendering: scene_info.rendering,
+            body_style: scene_info.body_style,
+            contact_solver: ContactSolver::new(scene_info.solver, scene_info.position_correction),
+            constraints: ConstraintSet::new(constraints),
+            joints: JointSet::default(),
+            collision_events: CollisionEventTracker::default(),
+            collision_callbacks: vec![],
+            triggers: scene_info.triggers,
+            pair_filter: PairFilter::new(never_collide),
+            sensor_events: SensorTracker::default(),
+            sensor_callbacks: vec![],
+            sync_to_gpu: false,
+            unread_buffers: None,
+            body_set_changed: false,
+            step_count: 0,
+            diagnostics,
+            sanity: SanityMonitor::new(scene_info.sanity),
+            paused: false,
+            recorder: None,
+            timeline: Timeline::new(scene_info.timeline),
+            seeking: false,
+            mouse_joint: None,
+        };
+        let initial = scene.snapshot();
+        scene.timeline.record(&initial);
+        scene
+    }
+
+    pub fn bodies(&self) -> &[RigidBody] {
+        &self.objects
+    }
+
+    pub fn body(&self, id: RBid) -> Option<&RigidBody> {
+        self.objects.get(id)
+    }
+
+    #[doc = "Mutable access to a body. Any change is uploaded to the GPU buffers before the next dispatch, and drawn after the next step."]
+    pub fn body_mut(&mut self, id: RBid) -> Option<&mut RigidBody> {
+        self.read_back();
+        if !self.objects.contains(id) {
+            return None;
+        }
+        self.sync_to_gpu = true;
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.mark_edited(id);
+        }
+        self.objects.get_mut(id)
+    }
+
+    fn update_body(&mut self, id: RBid, update: impl FnOnce(&mut RigidBody)) {
+        let Some(body) = self.objects.get_mut(id) else {
+            warn!("no body with id={}", id);
+            return;
+        };
+        update(body);
+        self.sync_to_gpu = true;
+        self.recreate_polygons();
+    }
+
+    #[doc = "Adds a body to the running scene and returns its new handle"]
+    pub fn spawn(&mut self, body: RigidBody) -> RBid {
+        self.record(|| ReplayEvent::Spawn(body.clone()));
+        let id = self.objects.insert(body);
+        self.body_set_changed = true;
+        self.recreate_polygons();
+        id
+    }
+
+    #[doc = "Removes a body from the running scene, along with its constraints. Joints attached to it break, and its handle becomes stale."]
+    pub fn despawn(&mut self, id: RBid) -> Option<RigidBody> {
+        self.record(|| ReplayEvent::Despawn(id));
+        let body = self.objects.remove(id)?;
+        self.constraints.remove_body(id);
+        self.joints.remove_body(id);
+        self.pair_filter.remove_body(id);
+        self.contact_solver.remove_body(id);
+        if self.mouse_joint.is_some_and(|joint| joint.body == id) {
+            self.mouse_joint = None;
+        }
+        self.body_set_changed = true;
+        self.recreate_polygons();
+        Some(body)
+    }
+
+    #[doc = "Whether bodies were spawned or despawned, or `dt` or gravity changed, since the last call, meaning the GPU buffers and command buffer are stale"]
+    pub fn take_body_set_changed(&mut self) -> bool {
+        std::mem::take(&mut self.body_set_changed)
+    }
+
+    #[doc = "Instantly changes the velocity of a body by `impulse` divided by its mass"]
+    pub fn apply_impulse(&mut self, id: RBid, impulse: FVec2) {
+        self.record(|| ReplayEvent::ApplyImpulse { body: id, impulse });
+        self.update_body(id, |body| {
+            let updated_velocity = body.get_velocity() + impulse * body.get_inverse_mass();
+            body.update_velocity(updated_velocity);
+        });
+    }
+
+    #[doc = "Applies `force` to a body for the duration of one step"]
+    pub fn apply_force(&mut self, id: RBid, force: FVec2) {
+        let dt = self.dt;
+        self.apply_impulse(id, force * dt);
+    }
+
+    pub fn set_velocity(&mut self, id: RBid, velocity: FVec2) {
+        self.record(|| ReplayEvent::SetVelocity { body: id, velocity });
+        self.update_body(id, |body| body.update_velocity(velocity));
+    }
+
+    #[doc = "Moves a body without changing its velocity. Nothing in between is swept, so it can end up overlapping other bodies."]
+    pub fn teleport(&mut self, id: RBid, position: FVec2) {
+        self.record(|| ReplayEvent::Teleport { body: id, position });
+        self.update_body(id, |body| body.update_position(position));
+    }
+
+    #[doc = "Connects two bodies, or a body and a fixed point. Returns the index of the constraint."]
+    pub fn add_constraint(&mut self, constraint: Constraint) -> usize {
+        self.record(|| ReplayEvent::AddConstraint(constraint));
+        self.constraints.add(constraint)
+    }
+
+    pub fn get_constraints(&self) -> &[Constraint] {
+        self.constraints.get_constraints()
+    }
+
+    #[doc = "Adds a revolute, weld or prismatic joint. The current relative angle of the bodies becomes the joint's rest angle. Joints are only solved on the CPU backend."]
+    pub fn add_joint(&mut self, joint_def: JointDef) -> JointHandle {
+        self.record(|| ReplayEvent::AddJoint(joint_def));
+        self.joints.add(joint_def, &self.objects)
+    }
+
+    #[doc = "Starts dragging a body by `point`, in world coordinates. Any body already held is let go. Unlike constraints, the mouse joint is not part of snapshots, so restoring one lets go too."]
+    pub fn grab(&mut self, id: RBid, point: FVec2, info: MouseJointInfo) {
+        self.record(|| ReplayEvent::Grab {
+            body: id,
+            point,
+            info,
+        });
+        self.mouse_joint = match self.objects.get(id) {
+            Some(body) => Some(MouseJoint::new(body, point, info)),
+            None => {
+                warn!("no body with id={}", id);
+                None
+            }
+        };
+    }
+
+    #[doc = "Moves the point the held body is pulled towards"]
+    pub fn drag_to(&mut self, point: FVec2) {
+        if self.mouse_joint.is_none() {
+            return;
+        }
+        self.record(|| ReplayEvent::DragTo(point));
+        if let Some(joint) = self.mouse_joint.as_mut() {
+            joint.target = point;
+        }
+    }
+
+    pub fn release(&mut self) {
+        if self.mouse_joint.is_none() {
+            return;
+        }
+        self.record(|| ReplayEvent::Release);
+        self.mouse_joint = None;
+    }
+
+    pub fn get_mouse_joint(&self) -> Option<&MouseJoint> {
+        self.mouse_joint.as_ref()
+    }
+
+    fn apply_mouse_joint(&mut self) {
+        let Some(joint) = self.mouse_joint else {
+            return;
+        };
+        if let Some(body) = self.objects.get_mut(joint.body) {
+            joint.apply(body, self.dt);
+            self.sync_to_gpu = true;
+        }
+    }
+
+    pub fn get_joint_def(&self, handle: JointHandle) -> Option<JointDef> {
+        self.joints.get_def(handle)
+    }
+
+    pub fn is_joint_broken(&self, handle: JointHandle) -> bool {
+        self.joints.is_broken(handle)
+    }
+
+    #[doc = "Force applied by the joint to its second body during the last step"]
+    pub fn get_joint_reaction_force(&self, handle: JointHandle) -> Option<FVec2> {
+        self.joints.get_reaction_force(handle, self.dt)
+    }
+
+    #[doc = "Torque applied by the joint to its second body during the last step"]
+    pub fn get_joint_reaction_torque(&self, handle: JointHandle) -> Option<f32> {
+        self.joints.get_reaction_torque(handle, self.dt)
+    }
+
+    #[doc = "Registers a callback that is called with every collision event, right after the step that produced it"]
+    pub fn on_collision(&mut self, callback: impl FnMut(&CollisionEvent) + 'static) {
+        self.collision_callbacks.push(Box::new(callback));
+    }
+
+    #[doc = "Collision events of the last step. They are replaced by the next step."]
+    pub fn get_collision_events(&self) -> &[CollisionEvent] {
+        self.collision_events.get_events()
+    }
+
+    pub fn drain_collision_events(&mut self) -> Vec<CollisionEvent> {
+        self.collision_events.drain()
+    }
+
+    fn record_collision_events(&mut self, contacts: Vec<CollisionEvent>) {
+        self.collision_events.record(contacts);
+        for callback in self.collision_callbacks.iter_mut() {
+            for event in self.collision_events.get_events() {
+                callback(event);
+            }
+        }
+    }
+
+    #[doc = "Adds a fixed region that reports bodies entering and leaving it. Returns its index, used in `Sensor::Trigger`."]
+    pub fn add_trigger(&mut self, trigger: Trigger) -> usize {
+        self.record(|| ReplayEvent::AddTrigger(trigger));
+        self.triggers.push(trigger);
+        self.triggers.len() - 1
+    }
+
+    pub fn get_triggers(&self) -> &[Trigger] {
+        &self.triggers
+    }
+
+    #[doc = "Registers a callback that is called when a body enters or leaves a sensor body or a trigger"]
+    pub fn on_sensor(&mut self, callback: impl FnMut(&SensorEvent) + 'static) {
+        self.sensor_callbacks.push(Box::new(callback));
+    }
+
+    #[doc = "Sensor events of the last step. They are replaced by the next step."]
+    pub fn get_sensor_events(&self) -> &[SensorEvent] {
+        self.sensor_events.get_events()
+    }
+
+    pub fn drain_sensor_events(&mut self) -> Vec<SensorEvent> {
+        self.sensor_events.drain()
+    }
+
+    fn record_sensor_events(&mut self, sensor_collisions: &[Collision]) {
+        let overlaps: Vec<(Sensor, RBid)> = sensor_collisions
             .iter()
-            .map(|body| body.to_polygon())
+            .filter_map(|collision| match collision.get_collision_type() {
+                CollisionObjectType::Sensor(sensor) => {
+                    Some((sensor, collision.get_primary()?.get_id()))
+                }
+                _ => None,
+            })
             .collect();
+        self.sensor_events.record(overlaps);
+        for callback in self.sensor_callbacks.iter_mut() {
+            for event in self.sensor_events.get_events() {
+                callback(event);
+            }
+        }
+    }
+
+    #[doc = "Stops two bodies from ever colliding, whatever their collision layers"]
+    pub fn add_never_collide(&mut self, body_a: RBid, body_b: RBid) {
+        self.record(|| ReplayEvent::AddNeverCollide(body_a, body_b));
+        self.pair_filter.add_never_collide(body_a, body_b);
+    }
+
+    #[doc = "First body hit by a ray from `origin` along `direction`, within `max_length`"]
+    pub fn raycast(
+        &self,
+        origin: FVec2,
+        direction: FVec2,
+        max_length: f32,
+        filter: &QueryFilter,
+    ) -> Option<RaycastHit> {
+        query::raycast(&self.objects, origin, direction, max_length, filter)
+    }
+
+    #[doc = "Bodies containing `point`"]
+    pub fn query_point(&self, point: FVec2, filter: &QueryFilter) -> Vec<RBid> {
+        query::query_point(&self.objects, point, filter)
+    }
+
+    #[doc = "Bodies overlapping the axis-aligned box from `min` to `max`"]
+    pub fn query_aabb(&self, min: FVec2, max: FVec2, filter: &QueryFilter) -> Vec<RBid> {
+        query::query_aabb(&self.objects, min, max, filter)
+    }
+
+    #[doc = "Bodies overlapping a circle at `center`"]
+    pub fn overlap_circle(&self, center: FVec2, radius: f32, filter: &QueryFilter) -> Vec<RBid> {
+        query::overlap_circle(&self.objects, center, radius, filter)
+    }
+
+    #[doc = "Energy, momentum and centre of mass right now"]
+    pub fn measure_diagnostics(&self) -> DiagnosticsSample {
+        DiagnosticsSample::measure(&self.objects, self.gravity, self.step_count)
+    }
 
-        let mut objects_hash: HashMap<u8, (RigidBody, Polygon)> =
-            HashMap::with_capacity_and_hasher(scene_info.objects.len(), RandomState::new());
-        for (rigidbody, polygon) in std::iter::zip(&scene_info.objects, polygons) {
-            objects_hash.insert(rigidbody.get_id(), (rigidbody.clone(), polygon));
+    #[doc = "Samples taken every `DiagnosticsInfo::interval` steps, and their drift from the first step"]
+    pub fn get_diagnostics(&self) -> &Diagnostics {
+        &self.diagnostics
+    }
+
+    pub fn get_step_count(&self) -> u64 {
+        self.step_count
+    }
+
+    #[doc = "Captures the simulation state so it can be resumed later with `restore`, possibly in another process"]
+    pub fn snapshot(&self) -> SceneSnapshot {
+        let mut never_collide = self.pair_filter.get_never_collide();
+        never_collide.sort();
+        SceneSnapshot {
+            version: SNAPSHOT_VERSION,
+            step_count: self.step_count,
+            dt: self.dt,
+            gravity: self.gravity,
+            objects: self.objects.clone(),
+            constraints: self.constraints.clone(),
+            joints: self.joints.clone(),
+            contact_solver: self.contact_solver.clone(),
+            collision_events: self.collision_events.clone(),
+            sensor_events: self.sensor_events.clone(),
+            triggers: self.triggers.clone(),
+            never_collide,
         }
+    }
 
-        Self {
-            objects: scene_info.objects,
-            dt: scene_info.dt,
-            objects_hash,
-            gravity: scene_info.gravity,
+    #[doc = "Replaces the simulation state with `snapshot`. The backend, callbacks and diagnostics are kept; the sanity history is cleared. On the GPU backend the compute buffers are rebuilt from the restored state before the next dispatch."]
+    pub fn restore(&mut self, snapshot: &SceneSnapshot) {
+        self.record(|| ReplayEvent::Restore(Box::new(snapshot.clone())));
+        let snapshot = snapshot.clone();
+        self.step_count = snapshot.step_count;
+        self.dt = snapshot.dt;
+        self.gravity = snapshot.gravity;
+        self.objects = snapshot.objects;
+        self.constraints = snapshot.constraints;
+        self.joints = snapshot.joints;
+        self.contact_solver = snapshot.contact_solver;
+        self.collision_events = snapshot.collision_events;
+        self.sensor_events = snapshot.sensor_events;
+        self.triggers = snapshot.triggers;
+        self.pair_filter = PairFilter::new(snapshot.never_collide);
+        self.mouse_joint = None;
+        self.sanity.clear_history();
+        // NOTE: The body count and constraints may differ from the current buffers, so they are
+        // rebuilt rather than written in place.
+        self.body_set_changed = true;
+        self.sync_to_gpu = true;
+        self.recreate_polygons();
+    }
+
+    pub fn get_sanity(&self) -> &SanityMonitor {
+        &self.sanity
+    }
+
+    pub fn set_sanity_info(&mut self, info: SanityInfo) {
+        self.record(|| ReplayEvent::SetSanityInfo(info.clone()));
+        self.sanity.set_info(info);
+    }
+
+    #[doc = "Whether stepping is stopped, either by `pause` or by a sanity check failing with `SanityResponse::Pause`"]
+    pub fn is_paused(&self) -> bool {
+        self.paused
+    }
+
+    #[doc = "Stops `step` and `update_with_buffers` from advancing the scene until `resume` is called"]
+    pub fn pause(&mut self) {
+        if !self.paused {
+            self.record(|| ReplayEvent::Pause);
+            self.paused = true;
+        }
+    }
+
+    pub fn resume(&mut self) {
+        if self.paused {
+            self.record(|| ReplayEvent::Resume);
+            self.paused = false;
+        }
+    }
+
+    pub fn get_gravity(&self) -> f32 {
+        self.gravity
+    }
+
+    pub fn set_gravity(&mut self, gravity: f32) {
+        self.record(|| ReplayEvent::SetGravity(gravity));
+        self.gravity = gravity;
+        // NOTE: Gravity and dt are push constants, so the compute command buffer is rebuilt.
+        self.body_set_changed = true;
+    }
+
+    pub fn get_dt(&self) -> f32 {
+        self.dt
+    }
+
+    pub fn set_dt(&mut self, dt: f32) {
+        self.record(|| ReplayEvent::SetDt(dt));
+        self.dt = dt;
+        self.body_set_changed = true;
+    }
+
+    pub fn set_solver_info(&mut self, info: SolverInfo) {
+        self.record(|| ReplayEvent::SetSolverInfo(info));
+        self.contact_solver.set_info(info);
+    }
+
+    pub fn get_timeline(&self) -> &Timeline {
+        &self.timeline
+    }
+
+    pub fn set_timeline_info(&mut self, info: TimelineInfo) {
+        self.timeline.set_info(info);
+    }
+
+    #[doc = "Pauses and moves the scene to `step`, clamped to the range kept by the timeline. The keyframe before `step` is restored and the steps after it are re-simulated on the CPU, so collision and sensor callbacks fire again for those steps. Changes made between keyframes, such as spawns, are only kept if a keyframe was taken after them."]
+    pub fn seek(&mut self, step: u64) {
+        let Some(start) = self.timeline.get_start() else {
+            return;
+        };
+        self.read_back();
+        let target = step.clamp(start, self.timeline.get_head());
+        self.pause();
+        // NOTE: Stepping on from the current state is only valid when no keyframe lies between it
+        // and the target.
+        let keyframe_step = self.timeline.get_keyframe_step(target).unwrap_or(start);
+        if target < self.step_count || keyframe_step > self.step_count {
+            match self.timeline.get_keyframe(target) {
+                Some(keyframe) => self.restore(&keyframe),
+                None => return,
+            }
+        }
+        self.seeking = true;
+        while self.step_count < target {
+            self.step_once();
+        }
+        self.seeking = false;
+        // NOTE: The steps above ran on the CPU, so the GPU buffers are refreshed from them.
+        self.sync_to_gpu = true;
+    }
+
+    #[doc = "Starts recording every outside change and a checksum after each step, so the run can be replayed with `ReplayPlayer`. Runs on the GPU backend can be recorded, but only CPU runs replay exactly."]
+    pub fn start_recording(&mut self) {
+        if self.backend == Backend::Gpu {
+            warn!("recording on the GPU backend; the replay runs on the CPU and will diverge");
+        }
+        self.read_back();
+        self.recorder = Some(ReplayRecorder::new(
+            self.snapshot(),
+            self.sanity.get_info().clone(),
+        ));
+    }
+
+    pub fn is_recording(&self) -> bool {
+        self.recorder.is_some()
+    }
+
+    pub fn stop_recording(&mut self) -> Option<ReplayRecording> {
+        self.read_back();
+        let step_count = self.step_count;
+        let mut recorder = self.recorder.take()?;
+        recorder.flush_edited(step_count, &self.objects);
+        Some(recorder.finish())
+    }
+
+    // NOTE: Bodies edited through `body_mut` are stored first, so events stay in the order they
+    // happened.
+    fn record(&mut self, event: impl FnOnce() -> ReplayEvent) {
+        self.read_back();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.flush_edited(self.step_count, &self.objects);
+            recorder.push(self.step_count, event());
+        }
+    }
+
+    fn begin_step(&mut self) {
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.flush_edited(self.step_count, &self.objects);
         }
     }
 
+    #[doc = "Writes the recorded steps, and the failing state after them, to `path` as JSON"]
+    pub fn write_sanity_dump(
+        &self,
+        violations: &[SanityViolation],
+        path: impl AsRef<std::path::Path>,
+    ) -> anyhow::Result<()> {
+        let history = self.sanity.get_history();
+        // NOTE: The flip in `with_info` is applied to a copy of the bodies, so positions are
+        // already in scene file coordinates.
+        let objects = history
+            .front()
+            .map(|record| record.bodies.clone())
+            .unwrap_or_else(|| self.objects.to_vec());
+        let dump = SanityDump {
+            objects,
+            constraints: self.constraints.get_constraints(),
+            triggers: &self.triggers,
+            never_collide: self.pair_filter.get_never_collide(),
+            dt: self.dt,
+            gravity: self.gravity,
+            failed_step: self.step_count,
+            violations,
+            failed_bodies: &self.objects,
+            history,
+        };
+        std::fs::write(path, serde_json::to_string_pretty(&dump)?)?;
+        Ok(())
+    }
+
+    // NOTE: Runs on the CPU after each step on either backend, or after each read-back when the
+    // GPU backend renders from its own buffers. Clamped bodies are uploaded before the next
+    // dispatch.
+    fn check_sanity(&mut self) {
+        let violations = self.sanity.check(&self.objects);
+        if violations.is_empty() {
+            self.sanity.record(self.step_count, &self.objects);
+            return;
+        }
+        for violation in &violations {
+            error!(
+                "sanity check failed at step {}: {}",
+                self.step_count, violation
+            );
+        }
+
+        let response = self.sanity.get_info().response;
+        if response == SanityResponse::Clamp {
+            self.sanity.clamp(self.objects.bodies_mut(), &violations);
+            self.sync_to_gpu = true;
+            self.sanity.record(self.step_count, &self.objects);
+            return;
+        }
+        if let Some(path) = self.sanity.get_info().dump_path.as_ref() {
+            match self.write_sanity_dump(&violations, path) {
+                Ok(()) => error!("scene state dumped to {}", path.display()),
+                Err(e) => error!("failed to write sanity dump to {}: {}", path.display(), e),
+            }
+        }
+        match response {
+            SanityResponse::Abort => panic!(
+                "sanity check failed at step {}: {}",
+                self.step_count, violations[0]
+            ),
+            _ => self.paused = true,
+        }
+    }
+
+    fn finish_step(&mut self) {
+        self.step_count += 1;
+        self.analyze_step();
+    }
+
+    #[doc = "Sanity checks, checksums, keyframes and diagnostics for the step the bodies are at"]
+    fn analyze_step(&mut self) {
+        self.check_sanity();
+        if let Some(recorder) = self.recorder.as_mut() {
+            recorder.push_checksum(&self.objects);
+        }
+        if !self.seeking {
+            self.timeline.advance_head(self.step_count);
+            if self.timeline.is_due(self.step_count) {
+                let keyframe = self.snapshot();
+                self.timeline.record(&keyframe);
+            }
+        }
+        if self.diagnostics.is_due(self.step_count) {
+            let sample = self.measure_diagnostics();
+            self.diagnostics.record(sample);
+        }
+        self.recreate_polygons();
+    }
+
+    pub fn get_backend(&self) -> Backend {
+        self.backend
+    }
+
+    pub fn get_rendering(&self) -> Rendering {
+        self.rendering
+    }
+
+    pub fn set_rendering(&mut self, rendering: Rendering) {
+        self.rendering = rendering;
+    }
+
+    pub fn get_body_style(&self) -> BodyStyle {
+        self.body_style
+    }
+
+    pub fn set_body_style(&mut self, body_style: BodyStyle) {
+        self.body_style = body_style;
+    }
+
+    #[doc = "Returns every overlapping pair of objects, every object outside `WORLD_BOUNDS`, and every overlap with a sensor or trigger"]
+    pub fn get_collisions(&self) -> Vec<Collision> {
+        let mut collisions: Vec<Collision> =
+            get_sensor_collisions(&self.objects, &self.triggers, &self.pair_filter);
+        for (idx, primary) in self.objects.iter().enumerate() {
+            if let Some(world_collision) = primary.check_world_collisions() {
+                collisions.push(world_collision);
+            }
+            for secondary in &self.objects[idx + 1..] {
+                if !primary.is_sensor()
+                    && !secondary.is_sensor()
+                    && self.pair_filter.should_collide(primary, secondary)
+                    && primary.collides_with(secondary)
+                {
+                    collisions.push(Collision::new(
+                        CollisionObjectType::Object,
+                        Some(primary.clone()),
+                        Some(secondary.clone()),
+                    ));
+                }
+            }
+        }
+
+        collisions
+    }
+
+    #[doc = "Runs `step_once` unless the scene is paused"]
+    pub fn step(&mut self) {
+        if !self.paused {
+            self.step_once();
+        }
+    }
+
+    #[doc = "Advances the scene by one `dt` on the CPU, even while paused: gravity and springs, contact and constraint resolution, position integration with swept tests for fast bodies, then penetration and constraint drift correction."]
+    pub fn step_once(&mut self) {
+        self.begin_step();
+        self.apply_mouse_joint();
+        for obj in self.objects.bodies_mut().iter_mut() {
+            let updated_velocity = obj.get_velocity() + FVec2::new(0., self.gravity * self.dt);
+            obj.update_velocity(updated_velocity);
+        }
+        self.constraints
+            .apply_spring_forces(self.objects.bodies_mut(), self.dt);
+
+        let collisions = self.get_collisions();
+        let mut manifolds: Vec<ContactManifold> = collisions
+            .iter()
+            .flat_map(ContactManifold::from_collision)
+            .collect();
+        self.contact_solver
+            .solve(self.objects.bodies_mut(), &mut manifolds, self.dt);
+        self.constraints.solve_velocities(
+            self.objects.bodies_mut(),
+            self.contact_solver.get_info().iterations,
+        );
+        self.joints.solve_velocities(
+            self.objects.bodies_mut(),
+            self.dt,
+            self.contact_solver.get_info().iterations,
+        );
+
+        // NOTE: Fast bodies and bullets, and whatever they are about to hit, move up to their first
+        // contact so they cannot skip past it within one step. There the contact takes away their
+        // approach, and the rest of the step is spent moving with what is left, e.g. sliding along it.
+        let swept: Vec<bool> = self
+            .objects
+            .iter()
+            .map(|obj| needs_ccd(obj, self.dt))
+            .collect();
+        let any_swept = swept.contains(&true);
+        let impacts: Vec<Option<Impact>> = (0..self.objects.len())
+            .map(|idx| match any_swept {
+                true => earliest_impact(&self.objects, idx, self.dt, &self.pair_filter, &swept),
+                false => None,
+            })
+            .collect();
+        // NOTE: Swept contacts are reported alongside the discrete ones, so a fast body raises the
+        // same events as a slow one.
+        let contacts: Vec<CollisionEvent> = manifolds
+            .iter()
+            .map(CollisionEvent::from_manifold)
+            .chain(impacts.iter().enumerate().filter_map(|(idx, impact)| {
+                Some(CollisionEvent::from_impact(
+                    &self.objects,
+                    idx,
+                    self.dt,
+                    impact.as_ref()?,
+                ))
+            }))
+            .collect();
+        self.record_collision_events(contacts);
+        let motions: Vec<(FVec2, FVec2)> = impacts
+            .iter()
+            .enumerate()
+            .map(|(idx, impact)| {
+                let velocity = self.objects[idx].get_velocity();
+                let Some(impact) = impact else {
+                    return (velocity * self.dt, velocity);
+                };
+                let velocity_after = velocity_after_impact(&self.objects, idx, impact);
+                let displacement = velocity * (self.dt * impact.time)
+                    + velocity_after * (self.dt * (1. - impact.time));
+                (displacement, velocity_after)
+            })
+            .collect();
+        for (obj, (displacement, velocity)) in self.objects.bodies_mut().iter_mut().zip(motions) {
+            obj.update_position(obj.get_position() + displacement);
+            obj.update_velocity(velocity);
+            let updated_angle = obj.get_angle() + obj.get_angular_velocity() * self.dt;
+            obj.update_angle(updated_angle);
+        }
+        self.contact_solver
+            .correct_positions(self.objects.bodies_mut(), &manifolds);
+        let position_correction = self.contact_solver.get_position_correction();
+        self.constraints
+            .correct_positions(self.objects.bodies_mut(), &position_correction);
+        self.joints
+            .correct_positions(self.objects.bodies_mut(), &position_correction);
+        self.record_sensor_events(&collisions);
+        self.finish_step();
+    }
+
+    #[doc = "Never-collide pairs as object indices, as the compute shader addresses bodies by index"]
+    fn get_never_collide_indices(&self) -> Vec<[u32; 2]> {
+        let index_of = |id: RBid| self.objects.get_slot(id);
+        self.pair_filter
+            .get_never_collide()
+            .into_iter()
+            .filter_map(|(body_a, body_b)| {
+                Some([index_of(body_a)? as u32, index_of(body_b)? as u32])
+            })
+            .collect()
+    }
+
     pub fn return_compute_shader_buffers(
         &self,
         memory_allocator: Arc<GenericMemoryAllocator<FreeListAllocator>>,
     ) -> RuntimeBuffers {
+        // NOTE: Empty buffers cannot be created, so a scene whose bodies were all despawned gets one
+        // placeholder body. `num_objects` stays 0, so the shader never reads it.
+        let objects = match self.objects.is_empty() {
+            true => vec![RigidBody::from(Circle::default())],
+            false => self.objects.to_vec(),
+        };
         let objects_positions = Buffer::from_iter(
             memory_allocator.clone(),
             BufferCreateInfo {
-                usage: BufferUsage::STORAGE_BUFFER,
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::VERTEX_BUFFER,
                 ..Default::default()
             },
             AllocationCreateInfo {
@@ -88,10 +990,7 @@ impl Scene {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            self.objects
-                .clone()
-                .iter()
-                .map(|obj| obj.get_position().as_array()),
+            objects.iter().map(|obj| obj.get_position().as_array()),
         )
         .unwrap();
         let objects_velocities = Buffer::from_iter(
@@ -105,16 +1004,13 @@ impl Scene {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            self.objects
-                .clone()
-                .iter()
-                .map(|obj| obj.get_velocity().as_array()),
+            objects.iter().map(|obj| obj.get_velocity().as_array()),
         )
         .unwrap();
         let objects_radii = Buffer::from_iter(
-            memory_allocator,
+            memory_allocator.clone(),
             BufferCreateInfo {
-                usage: BufferUsage::STORAGE_BUFFER,
+                usage: BufferUsage::STORAGE_BUFFER | BufferUsage::VERTEX_BUFFER,
                 ..Default::default()
             },
             AllocationCreateInfo {
@@ -122,67 +1018,205 @@ impl Scene {
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            self.objects
-                .clone()
+            objects
                 .iter()
-                .map(|obj| [obj.get_radius(), 0.]),
+                .map(|obj| [obj.get_radius(), obj.get_gpu_flags() as f32]),
+        )
+        .unwrap();
+        // NOTE: Only drawn from, never read by the compute shader.
+        let objects_colors = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            objects.iter().map(|obj| obj.get_color().to_array()),
         )
         .unwrap();
 
-        RuntimeBuffers {
-            objects_positions,
-            objects_velocities,
-            objects_radii,
+        // NOTE: Empty buffers cannot be created, so pad with one unused constraint. The shader only reads `num_constraints`.
+        let mut gpu_constraints = self.constraints.to_gpu_constraints(&self.objects);
+        if gpu_constraints.is_empty() {
+            gpu_constraints.push(GpuConstraint {
+                kind: 0,
+                body_a: 0,
+                body_b: -1,
+                params: [0.; 3],
+                anchor: [0.; 2],
+            });
         }
-    }
+        let constraints = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            gpu_constraints,
+        )
+        .unwrap();
 
-    pub fn return_objects_as_vertex_buffer(
-        &self,
-        device: Arc<Device>,
-    ) -> Subbuffer<[CustomVertex]> {
-        let vertex_buffer_data = {
-            let mut buffer_data: Vec<CustomVertex> =
-                Vec::with_capacity(self.objects_hash.len() * 3);
-            for (_, (_, polygon)) in &self.objects_hash {
-                buffer_data = [buffer_data, polygon.destructure_into_list()].concat();
-            }
-            buffer_data
-        };
-        Buffer::from_iter(
-            create_memory_allocator(device.clone()),
+        let collision_filters = Buffer::from_iter(
+            memory_allocator.clone(),
             BufferCreateInfo {
-                usage: BufferUsage::VERTEX_BUFFER,
+                usage: BufferUsage::STORAGE_BUFFER,
                 ..Default::default()
             },
             AllocationCreateInfo {
-                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
                     | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
                 ..Default::default()
             },
-            vertex_buffer_data.clone(),
+            objects.iter().map(|obj| {
+                let filter = obj.get_collision_filter();
+                [filter.category, filter.mask]
+            }),
         )
-        .expect("scene: could not produce vertex buffer from objects")
+        .unwrap();
+        // NOTE: Padded like the constraints buffer; the shader only reads `num_never_collide`.
+        let mut never_collide = self.get_never_collide_indices();
+        if never_collide.is_empty() {
+            never_collide.push([0, 0]);
+        }
+        let never_collide = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            never_collide,
+        )
+        .unwrap();
+        let collision_event_count = Buffer::from_iter(
+            memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            [0u32],
+        )
+        .unwrap();
+        let collision_events = Buffer::from_iter(
+            memory_allocator,
+            BufferCreateInfo {
+                usage: BufferUsage::STORAGE_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_HOST
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            vec![GpuCollisionEvent::default(); MAX_GPU_COLLISION_EVENTS],
+        )
+        .unwrap();
+
+        RuntimeBuffers {
+            objects_positions,
+            objects_velocities,
+            objects_radii,
+            objects_colors,
+            constraints,
+            collision_event_count,
+            collision_events,
+            collision_filters,
+            never_collide,
+        }
+    }
+
+    #[doc = "Appends the vertices of every body and constraint to `vertices`. Reusing the same `Vec` every frame avoids reallocating it."]
+    pub fn write_vertices(&self, vertices: &mut Vec<CustomVertex>) {
+        for polygon in &self.polygons {
+            for triangle in polygon {
+                vertices.extend_from_slice(triangle);
+            }
+        }
+        self.write_link_vertices(vertices);
+    }
+
+    #[doc = "Appends the vertices of constraints, joints and the mouse joint, but not the bodies, to `vertices`"]
+    pub fn write_link_vertices(&self, vertices: &mut Vec<CustomVertex>) {
+        let segments = self
+            .constraints
+            .get_segments(&self.objects)
+            .into_iter()
+            .chain(self.joints.get_segments(&self.objects));
+        for (from, to) in segments {
+            let line = generate_line_triangles(from, to, CONSTRAINT_LINE_WIDTH, Color32::GRAY);
+            for triangle in &line {
+                vertices.extend_from_slice(triangle);
+            }
+        }
+        if let Some(joint) = self.mouse_joint {
+            if let Some(body) = self.objects.get(joint.body) {
+                let line = generate_line_triangles(
+                    joint.get_anchor(body),
+                    joint.target,
+                    CONSTRAINT_LINE_WIDTH,
+                    Color32::YELLOW,
+                );
+                for triangle in &line {
+                    vertices.extend_from_slice(triangle);
+                }
+            }
+        }
+    }
+
+    pub fn get_compute_constants(&self) -> update_cs::ComputeConstants {
+        let position_correction = self.contact_solver.get_position_correction();
+        update_cs::ComputeConstants {
+            gravity: self.gravity,
+            dt: self.dt,
+            num_objects: self.objects.len() as u32,
+            ccd_threshold: CCD_MOTION_THRESHOLD,
+            num_never_collide: self.get_never_collide_indices().len() as u32,
+            num_constraints: self.constraints.to_gpu_constraints(&self.objects).len() as u32,
+            slop: position_correction.slop,
+            correction_factor: match position_correction.method {
+                PositionCorrection::None => 0.,
+                _ => position_correction.correction_factor,
+            },
+            position_correction_method: position_correction.method.get_method_id(),
+        }
     }
 
     pub fn run(self) {
         let event_loop = EventLoop::new();
         let window_ctx = WindowContext::new(&event_loop);
         let vk_ctx = VulkanoContext::with_window_context(&window_ctx, &event_loop);
-        let push_constants = update_cs::ComputeConstants {
-            gravity: self.gravity,
-            dt: self.dt,
-            num_objects: self.objects.len() as u32,
-        };
+        if self.backend == Backend::Gpu && !self.joints.is_empty() {
+            warn!("joints are only solved on the CPU backend and will be ignored");
+        }
         let window_ctx_handler = WindowEventHandler::new(
             &event_loop,
             self.return_compute_shader_buffers(vk_ctx.get_memory_allocator()),
             vk_ctx,
             window_ctx,
-            push_constants,
+            self.get_compute_constants(),
         );
         window_ctx_handler.run_with_scene(self, event_loop);
     }
 
+    #[doc = "Runs `update_with_buffers_once` unless the scene is paused"]
     pub fn update_with_buffers(
         &mut self,
         device: Arc<Device>,
@@ -190,13 +1224,78 @@ impl Scene {
         compute_command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>,
         runtime_buffers: RuntimeBuffers,
     ) {
+        if !self.paused {
+            self.update_with_buffers_once(device, queue, compute_command_buffer, runtime_buffers);
+        }
+    }
+
+    #[doc = "Dispatches one step of `update_cs` and reads the bodies back, even while paused"]
+    pub fn update_with_buffers_once(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        compute_command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>,
+        runtime_buffers: RuntimeBuffers,
+    ) {
+        self.dispatch_with_buffers_once(device, queue, compute_command_buffer, runtime_buffers);
+        self.read_back();
+    }
+
+    #[doc = "Dispatches one step of `update_cs`, even while paused, and leaves its results in the buffers. Until `read_back` is called the bodies, events, timeline and diagnostics stay at the last step read back, though the step count moves on; editing the scene reads back first."]
+    pub fn dispatch_with_buffers_once(
+        &mut self,
+        device: Arc<Device>,
+        queue: Arc<Queue>,
+        compute_command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>,
+        runtime_buffers: RuntimeBuffers,
+    ) {
+        // NOTE: The mouse joint pulls on the body's current state, and recordings hold a
+        // checksum of every step, so both read back each step.
+        if self.mouse_joint.is_some() || self.recorder.is_some() {
+            self.read_back();
+        }
+        self.begin_step();
+        self.apply_mouse_joint();
+        self.write_to_buffers(&runtime_buffers);
+
         let future = sync::now(device.clone())
             .then_execute(queue.clone(), compute_command_buffer)
             .unwrap()
             .then_signal_fence_and_flush()
             .unwrap();
         future.wait(None).unwrap();
+        self.step_count += 1;
+        self.unread_buffers = Some(runtime_buffers);
+    }
 
+    // NOTE: The buffers are the source of truth on the GPU backend, so changes made through the
+    // body API are written back before dispatching.
+    #[doc = "Uploads bodies changed on the CPU since the last upload to the buffers. The GPU must not be using them."]
+    pub fn write_to_buffers(&mut self, runtime_buffers: &RuntimeBuffers) {
+        if !self.sync_to_gpu {
+            return;
+        }
+        let mut object_positions_writer = runtime_buffers.objects_positions.write().unwrap();
+        let mut object_velocities_writer = runtime_buffers.objects_velocities.write().unwrap();
+        let mut object_radii_writer = runtime_buffers.objects_radii.write().unwrap();
+        let mut object_colors_writer = runtime_buffers.objects_colors.write().unwrap();
+        let mut collision_filters_writer = runtime_buffers.collision_filters.write().unwrap();
+        for (idx, obj) in self.objects.iter().enumerate() {
+            let filter = obj.get_collision_filter();
+            object_positions_writer[idx] = obj.get_position().as_array();
+            object_velocities_writer[idx] = obj.get_velocity().as_array();
+            object_radii_writer[idx] = [obj.get_radius(), obj.get_gpu_flags() as f32];
+            object_colors_writer[idx] = obj.get_color().to_array();
+            collision_filters_writer[idx] = [filter.category, filter.mask];
+        }
+        self.sync_to_gpu = false;
+    }
+
+    #[doc = "Brings the bodies up to date with the buffers after `dispatch_with_buffers_once`, and records the events, checks and keyframes of the step they are now at. Contacts that began and ended between two read-backs are reported once."]
+    pub fn read_back(&mut self) {
+        let Some(runtime_buffers) = self.unread_buffers.take() else {
+            return;
+        };
         let binding = runtime_buffers.objects_positions.clone();
         let object_positions_reader = binding.read().unwrap();
         let binding = runtime_buffers.objects_velocities.clone();
@@ -205,17 +1304,35 @@ impl Scene {
             object_positions_reader.iter(),
             object_velocities_reader.iter(),
         )
+        .take(self.objects.len())
         .enumerate()
         {
-            self.objects[idx].update_position(updated_position.into());
-            self.objects[idx].update_velocity(updated_velocity.into());
+            self.objects.bodies_mut()[idx].update_position(updated_position.into());
+            self.objects.bodies_mut()[idx].update_velocity(updated_velocity.into());
         }
+        drop(object_positions_reader);
+        drop(object_velocities_reader);
+
+        // NOTE: The shader appends past the capacity without writing, so the count can exceed the buffer.
+        let mut event_count_writer = runtime_buffers.collision_event_count.write().unwrap();
+        let event_count = (event_count_writer[0] as usize).min(MAX_GPU_COLLISION_EVENTS);
+        event_count_writer[0] = 0;
+        drop(event_count_writer);
+        let contacts: Vec<CollisionEvent> = runtime_buffers.collision_events.read().unwrap()
+            [..event_count]
+            .iter()
+            .filter_map(|event| event.to_collision_event(&self.objects))
+            .collect();
+        self.record_collision_events(contacts);
+        let sensor_collisions =
+            get_sensor_collisions(&self.objects, &self.triggers, &self.pair_filter);
+        self.record_sensor_events(&sensor_collisions);
+
         // self.check_and_world_resolve_collisions();
-        self.recreate_hash();
+        self.analyze_step();
 
         info!("{:?}", {
             self.objects
-                .clone()
                 .iter()
                 .map(|obj| {
                     format!(
@@ -228,22 +1345,112 @@ impl Scene {
         });
     }
 
-    pub fn recreate_hash(&mut self) {
-        let polygons: Vec<Polygon> = self.objects.iter().map(|body| body.to_polygon()).collect();
+    pub fn recreate_polygons(&mut self) {
+        self.polygons = self.objects.iter().map(|body| body.to_polygon()).collect();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{Scene, SceneInfo};
+    use crate::{
+        physics::{
+            constraint::{Anchor, Constraint, ConstraintKind},
+            replay::{ReplayEvent, TimedReplayEvent},
+            rigidbody::RBid,
+            test_util::{moving_circle, two_body_scene},
+        },
+        FVec2,
+    };
 
-        let mut objects_as_hash: HashMap<u8, (RigidBody, Polygon)> =
-            HashMap::with_capacity_and_hasher(self.objects.len(), RandomState::new());
-        for (rigidbody, polygon) in std::iter::zip(&self.objects, polygons) {
-            objects_as_hash.insert(rigidbody.get_id(), (rigidbody.clone(), polygon));
-        }
+    #[test]
+    fn check_constraints_name_arena_handles() {
+        let rope = |body, anchor| Constraint {
+            body,
+            anchor: Anchor::Body(anchor),
+            kind: ConstraintKind::Rope { max_length: 1. },
+        };
+        // NOTE: Both circles from `moving_circle` carry the placeholder id.
+        let scene = Scene::with_info(SceneInfo {
+            objects: vec![moving_circle(-0.5, 0.), moving_circle(0.5, 0.)],
+            constraints: vec![
+                rope(RBid::new(0, 0), RBid::new(1, 0)),
+                rope(RBid::new(0, 0), RBid::new(7, 0)),
+            ],
+            ..Default::default()
+        });
+        let ids: Vec<RBid> = scene.bodies().iter().map(|body| body.get_id()).collect();
+
+        assert_eq!(ids, vec![RBid::new(0, 0), RBid::new(1, 0)]);
+        assert_eq!(scene.get_constraints(), &[rope(ids[0], ids[1])]);
+    }
+
+    #[test]
+    fn check_despawn_rebuilds_gpu_buffers() {
+        let mut scene = two_body_scene();
+        let (first, second) = (scene.bodies()[0].get_id(), scene.bodies()[1].get_id());
+        scene.take_body_set_changed();
+
+        scene.despawn(first);
+        // NOTE: The second body moved into the first GPU slot, so the buffers must be rebuilt.
+        assert!(scene.take_body_set_changed());
+        assert_eq!(scene.bodies()[0].get_id(), second);
+        assert_eq!(
+            scene.body(second).unwrap().get_position(),
+            FVec2::new(0.5, 0.)
+        );
+    }
+
+    #[test]
+    fn check_body_access_with_live_handle() {
+        let mut scene = two_body_scene();
+        let id = scene.bodies()[0].get_id();
+        assert_eq!(scene.body(id).map(|body| body.get_id()), Some(id));
+        // NOTE: Bodies handed out by `body_mut` are recorded as edited, like they are uploaded to the GPU.
+        scene.start_recording();
+        assert!(scene.body_mut(id).is_some());
+        let recording = scene.stop_recording().unwrap();
+        assert!(matches!(
+            recording.events.as_slice(),
+            [TimedReplayEvent { event: ReplayEvent::SetBody(body), .. }] if body.get_id() == id
+        ));
 
-        self.objects_hash = objects_as_hash;
+        scene.set_velocity(id, FVec2::new(0., 1.));
+        assert_eq!(scene.body(id).unwrap().get_velocity(), FVec2::new(0., 1.));
+        let inverse_mass = scene.body(id).unwrap().get_inverse_mass();
+        scene.apply_impulse(id, FVec2::new(1., 0.));
+        let velocity = scene.body(id).unwrap().get_velocity();
+        assert!((velocity - FVec2::new(inverse_mass, 1.)).magnitude() < 1e-5);
+        scene.apply_force(id, FVec2::new(1., 0.));
+        let velocity_change = scene.body(id).unwrap().get_velocity() - velocity;
+        assert!(
+            (velocity_change - FVec2::new(inverse_mass * scene.get_dt(), 0.)).magnitude() < 1e-5
+        );
+        scene.teleport(id, FVec2::new(0.3, 0.3));
+        assert_eq!(scene.body(id).unwrap().get_position(), FVec2::new(0.3, 0.3));
     }
 
-    pub fn recreate_objects_from_hash(&mut self) {
-        self.objects.clear();
-        for (rigidbody, _) in self.objects_hash.values() {
-            self.objects.push(rigidbody.clone());
+    #[test]
+    fn check_body_access_with_stale_handle() {
+        let mut scene = two_body_scene();
+        let id = scene.bodies()[0].get_id();
+        scene.despawn(id);
+        let other = scene.bodies()[0].clone();
+        scene.start_recording();
+
+        for stale in [id, RBid::new(7, 0)] {
+            assert!(scene.body(stale).is_none());
+            assert!(scene.body_mut(stale).is_none());
+            scene.apply_impulse(stale, FVec2::new(1., 0.));
+            scene.apply_force(stale, FVec2::new(1., 0.));
+            scene.set_velocity(stale, FVec2::new(0., 1.));
+            scene.teleport(stale, FVec2::new(0.3, 0.3));
         }
+        let recording = scene.stop_recording().unwrap();
+        assert!(!recording
+            .events
+            .iter()
+            .any(|timed| matches!(timed.event, ReplayEvent::SetBody(_))));
+        assert_eq!(scene.bodies(), &[other]);
     }
 }
@@ -0,0 +1,193 @@
+use std::collections::HashSet;
+
+use serde::{Deserialize, Serialize};
+
+use crate::FVec2;
+
+use super::{
+    collision::{Collision, CollisionObjectType, PairFilter},
+    query::circle_overlaps_aabb,
+    rigidbody::{RBid, RigidBody},
+};
+
+#[doc = "Region of a standalone trigger, in world coordinates"]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum TriggerShape {
+    Circle { center: FVec2, radius: f32 },
+    Aabb { min: FVec2, max: FVec2 },
+}
+
+#[doc = "Fixed region that reports which bodies enter and leave it, e.g. a target zone or a billiard pocket. Bodies pass through it."]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Trigger {
+    pub shape: TriggerShape,
+}
+
+impl Trigger {
+    pub fn overlaps(&self, body: &RigidBody) -> bool {
+        let position = body.get_position();
+        let radius = body.get_radius();
+        match self.shape {
+            TriggerShape::Circle {
+                center,
+                radius: trigger_radius,
+            } => (position - center).magnitude() <= radius + trigger_radius,
+            TriggerShape::Aabb { min, max } => circle_overlaps_aabb(position, radius, min, max),
+        }
+    }
+}
+
+#[doc = "What detected an overlap: a body with the sensor flag, or a trigger by its index in the scene"]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Sensor {
+    Body(RBid),
+    Trigger(usize),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SensorPhase {
+    Enter,
+    Exit,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SensorEvent {
+    pub sensor: Sensor,
+    pub body: RBid,
+    pub phase: SensorPhase,
+}
+
+pub type SensorCallback = Box<dyn FnMut(&SensorEvent)>;
+
+#[doc = "Returns a `Sensor` collision for every body overlapping a sensor body or a trigger. The visiting body is the primary."]
+pub fn get_sensor_collisions(
+    objects: &[RigidBody],
+    triggers: &[Trigger],
+    pair_filter: &PairFilter,
+) -> Vec<Collision> {
+    let mut collisions: Vec<Collision> = vec![];
+    for (idx, primary) in objects.iter().enumerate() {
+        for secondary in &objects[idx + 1..] {
+            if !(primary.is_sensor() || secondary.is_sensor())
+                || !pair_filter.should_collide(primary, secondary)
+                || !primary.collides_with(secondary)
+            {
+                continue;
+            }
+            // NOTE: When both are sensors, each detects the other.
+            if secondary.is_sensor() {
+                collisions.push(Collision::new(
+                    CollisionObjectType::Sensor(Sensor::Body(secondary.get_id())),
+                    Some(primary.clone()),
+                    Some(secondary.clone()),
+                ));
+            }
+            if primary.is_sensor() {
+                collisions.push(Collision::new(
+                    CollisionObjectType::Sensor(Sensor::Body(primary.get_id())),
+                    Some(secondary.clone()),
+                    Some(primary.clone()),
+                ));
+            }
+        }
+        for (trigger_idx, trigger) in triggers.iter().enumerate() {
+            if trigger.overlaps(primary) {
+                collisions.push(Collision::new(
+                    CollisionObjectType::Sensor(Sensor::Trigger(trigger_idx)),
+                    Some(primary.clone()),
+                    None,
+                ));
+            }
+        }
+    }
+
+    collisions
+}
+
+#[doc = "Turns the sensor overlaps found in each step into enter/exit events by comparing them with the previous step"]
+#[derive(Clone, Debug, Default, Serialize, Deserialize)]
+pub struct SensorTracker {
+    overlapping: Vec<(Sensor, RBid)>,
+    events: Vec<SensorEvent>,
+}
+
+impl SensorTracker {
+    #[doc = "Replaces the pending events with the changes between the previous overlaps and `overlaps`"]
+    pub fn record(&mut self, overlaps: Vec<(Sensor, RBid)>) {
+        let previous: HashSet<(Sensor, RBid)> = self.overlapping.iter().copied().collect();
+        let current: HashSet<(Sensor, RBid)> = overlaps.iter().copied().collect();
+
+        self.events.clear();
+        for (sensor, body) in overlaps.iter().filter(|pair| !previous.contains(pair)) {
+            self.events.push(SensorEvent {
+                sensor: *sensor,
+                body: *body,
+                phase: SensorPhase::Enter,
+            });
+        }
+        for (sensor, body) in self
+            .overlapping
+            .iter()
+            .filter(|pair| !current.contains(pair))
+        {
+            self.events.push(SensorEvent {
+                sensor: *sensor,
+                body: *body,
+                phase: SensorPhase::Exit,
+            });
+        }
+        self.overlapping = overlaps;
+    }
+    pub fn get_events(&self) -> &[SensorEvent] {
+        &self.events
+    }
+    pub fn drain(&mut self) -> Vec<SensorEvent> {
+        std::mem::take(&mut self.events)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{get_sensor_collisions, Sensor, SensorPhase, SensorTracker, Trigger, TriggerShape};
+    use crate::{
+        physics::{
+            collision::{CollisionObjectType, PairFilter},
+            rigidbody::RBid,
+            test_util::circle,
+        },
+        FVec2,
+    };
+
+    #[test]
+    fn check_trigger_enter_and_exit() {
+        let trigger = Trigger {
+            shape: TriggerShape::Aabb {
+                min: FVec2::new(0.5, 0.5),
+                max: FVec2::new(1., 1.),
+            },
+        };
+        let body = |x: f32| circle(FVec2::new(x, 0.6), FVec2::new(0., 0.), 0);
+        let overlaps = |x: f32| -> Vec<(Sensor, RBid)> {
+            get_sensor_collisions(&[body(x)], &[trigger], &PairFilter::default())
+                .iter()
+                .filter_map(|collision| match collision.get_collision_type() {
+                    CollisionObjectType::Sensor(sensor) => {
+                        Some((sensor, collision.get_primary()?.get_id()))
+                    }
+                    _ => None,
+                })
+                .collect()
+        };
+        let mut tracker = SensorTracker::default();
+
+        tracker.record(overlaps(0.));
+        assert!(tracker.get_events().is_empty());
+        tracker.record(overlaps(0.45));
+        assert_eq!(tracker.get_events()[0].phase, SensorPhase::Enter);
+        tracker.record(overlaps(0.7));
+        assert!(tracker.get_events().is_empty());
+        tracker.record(overlaps(0.));
+        assert_eq!(tracker.get_events()[0].phase, SensorPhase::Exit);
+        assert_eq!(tracker.get_events()[0].sensor, Sensor::Trigger(0));
+    }
+}
@@ -0,0 +1,598 @@
+use std::collections::HashMap;
+
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+
+use crate::FVec2;
+
+use super::{
+    collision::{Collision, CollisionObjectType},
+    lib::{
+        COEFF_RESTITUTION, PENETRATION_SLOP, POSITION_CORRECTION_FACTOR,
+        RESTITUTION_VELOCITY_THRESHOLD, WORLD_BOUNDS,
+    },
+    rigidbody::{RBid, RigidBody},
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SolverInfo {
+    pub iterations: u32,
+    pub warm_starting: bool,
+}
+
+impl Default for SolverInfo {
+    fn default() -> Self {
+        Self {
+            iterations: 8,
+            warm_starting: true,
+        }
+    }
+}
+
+#[doc = "How penetration is removed. Baumgarte feeds it back into the velocity solve as a bias; split impulse moves positions directly after integration and leaves velocities, and so energy, untouched."]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub enum PositionCorrection {
+    None,
+    Baumgarte,
+    #[default]
+    SplitImpulse,
+}
+
+impl PositionCorrection {
+    #[doc = "Matches the `POSITION_CORRECTION_*` constants of the compute shader"]
+    pub fn get_method_id(&self) -> u32 {
+        match self {
+            PositionCorrection::None => 0,
+            PositionCorrection::Baumgarte => 1,
+            PositionCorrection::SplitImpulse => 2,
+        }
+    }
+}
+
+#[doc = "Only penetration deeper than `slop` is corrected, and only `correction_factor` of it per step"]
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PositionCorrectionInfo {
+    pub method: PositionCorrection,
+    pub slop: f32,
+    pub correction_factor: f32,
+    pub iterations: u32,
+}
+
+impl Default for PositionCorrectionInfo {
+    fn default() -> Self {
+        Self {
+            method: PositionCorrection::default(),
+            slop: PENETRATION_SLOP,
+            correction_factor: POSITION_CORRECTION_FACTOR,
+            iterations: 3,
+        }
+    }
+}
+
+impl PositionCorrectionInfo {
+    #[doc = "Distance to remove this step for a contact with the given penetration"]
+    pub fn get_correction(&self, penetration: f32) -> f32 {
+        (penetration - self.slop).max(0.) * self.correction_factor
+    }
+}
+
+#[doc = "One of the four sides of `WORLD_BOUNDS`, named after the axis direction it faces outward to"]
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum Wall {
+    NegativeX,
+    PositiveX,
+    NegativeY,
+    PositiveY,
+}
+
+impl Wall {
+    #[doc = "Outward normal of the wall, i.e. pointing from the body into the wall"]
+    pub fn get_normal(&self) -> FVec2 {
+        match self {
+            Wall::NegativeX => FVec2::new(-1., 0.),
+            Wall::PositiveX => FVec2::new(1., 0.),
+            Wall::NegativeY => FVec2::new(0., -1.),
+            Wall::PositiveY => FVec2::new(0., 1.),
+        }
+    }
+    #[doc = "Wall that a contact with outward normal `normal` is against, going by its larger component"]
+    pub fn from_normal(normal: FVec2) -> Self {
+        match (
+            normal.x.abs() >= normal.y.abs(),
+            normal.x > 0.,
+            normal.y > 0.,
+        ) {
+            (true, true, _) => Wall::PositiveX,
+            (true, false, _) => Wall::NegativeX,
+            (false, _, true) => Wall::PositiveY,
+            (false, _, false) => Wall::NegativeY,
+        }
+    }
+    #[doc = "Returns the walls crossed by a circle, with the depth it went past each"]
+    pub fn get_penetrations(position: FVec2, radius: f32) -> Vec<(Wall, f32)> {
+        let mut penetrations = vec![];
+        let candidates = [
+            (
+                Wall::NegativeX,
+                WORLD_BOUNDS.0.start - (position.x - radius),
+            ),
+            (Wall::PositiveX, position.x + radius - WORLD_BOUNDS.0.end),
+            (
+                Wall::NegativeY,
+                WORLD_BOUNDS.1.start - (position.y - radius),
+            ),
+            (Wall::PositiveY, position.y + radius - WORLD_BOUNDS.1.end),
+        ];
+        for (wall, penetration) in candidates {
+            if penetration > 0. {
+                penetrations.push((wall, penetration));
+            }
+        }
+
+        penetrations
+    }
+    pub fn get_penetration(&self, position: FVec2, radius: f32) -> f32 {
+        match self {
+            Wall::NegativeX => WORLD_BOUNDS.0.start - (position.x - radius),
+            Wall::PositiveX => position.x + radius - WORLD_BOUNDS.0.end,
+            Wall::NegativeY => WORLD_BOUNDS.1.start - (position.y - radius),
+            Wall::PositiveY => position.y + radius - WORLD_BOUNDS.1.end,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum ContactTarget {
+    Body(RBid),
+    World(Wall),
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, PartialOrd, Ord, Serialize, Deserialize)]
+pub struct ContactKey {
+    primary: RBid,
+    target: ContactTarget,
+}
+
+#[derive(Clone, Debug)]
+pub struct ContactPoint {
+    pub position: FVec2,
+    pub penetration: f32,
+    normal_impulse: f32,
+    normal_mass: f32,
+    velocity_bias: f32,
+}
+
+impl ContactPoint {
+    fn new(position: FVec2, penetration: f32) -> Self {
+        Self {
+            position,
+            penetration,
+            normal_impulse: 0.,
+            normal_mass: 0.,
+            velocity_bias: 0.,
+        }
+    }
+    pub fn get_normal_impulse(&self) -> f32 {
+        self.normal_impulse
+    }
+}
+
+#[doc = "Contact between a body and either another body or a wall. The normal points from the primary to the target."]
+#[derive(Clone, Debug)]
+pub struct ContactManifold {
+    key: ContactKey,
+    normal: FVec2,
+    points: Vec<ContactPoint>,
+}
+
+impl ContactManifold {
+    #[doc = "Builds the manifolds described by a collision. World collisions produce one manifold per crossed wall."]
+    pub fn from_collision(collision: &Collision) -> Vec<ContactManifold> {
+        let Some(primary) = collision.get_primary() else {
+            return vec![];
+        };
+        match collision.get_collision_type() {
+            CollisionObjectType::Object => {
+                let Some(secondary) = collision.get_secondary() else {
+                    return vec![];
+                };
+                let vector_between_com = secondary.get_position() - primary.get_position();
+                let distance = vector_between_com.magnitude();
+                // NOTE: Coincident centres have no meaningful direction, so push along y.
+                let normal = if distance > f32::EPSILON {
+                    vector_between_com * (1. / distance)
+                } else {
+                    FVec2::new(0., 1.)
+                };
+                let penetration = primary.get_radius() + secondary.get_radius() - distance;
+                let position =
+                    primary.get_position() + normal * (primary.get_radius() - penetration / 2.);
+
+                vec![ContactManifold {
+                    key: ContactKey {
+                        primary: primary.get_id(),
+                        target: ContactTarget::Body(secondary.get_id()),
+                    },
+                    normal,
+                    points: vec![ContactPoint::new(position, penetration)],
+                }]
+            }
+            CollisionObjectType::Sensor(_) => vec![],
+            CollisionObjectType::World(_) => {
+                Wall::get_penetrations(primary.get_position(), primary.get_radius())
+                    .into_iter()
+                    .map(|(wall, penetration)| {
+                        let normal = wall.get_normal();
+                        let position = primary.get_position() + normal * primary.get_radius();
+                        ContactManifold {
+                            key: ContactKey {
+                                primary: primary.get_id(),
+                                target: ContactTarget::World(wall),
+                            },
+                            normal,
+                            points: vec![ContactPoint::new(position, penetration)],
+                        }
+                    })
+                    .collect()
+            }
+        }
+    }
+    pub fn get_primary(&self) -> RBid {
+        self.key.primary
+    }
+    pub fn get_target(&self) -> ContactTarget {
+        self.key.target
+    }
+    pub fn get_normal(&self) -> FVec2 {
+        self.normal
+    }
+    pub fn get_points(&self) -> &[ContactPoint] {
+        &self.points
+    }
+}
+
+#[doc = "Sequential-impulse contact solver. Accumulated normal impulses are clamped to be non-negative and cached between steps for warm starting."]
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ContactSolver {
+    info: SolverInfo,
+    position_correction: PositionCorrectionInfo,
+    #[serde(
+        serialize_with = "serialize_cached_impulses",
+        deserialize_with = "deserialize_cached_impulses"
+    )]
+    cached_impulses: HashMap<ContactKey, Vec<f32>>,
+}
+
+// NOTE: Stored as a list sorted by key, since JSON maps need string keys and the encoding of equal
+// caches should not depend on hash order.
+fn serialize_cached_impulses<S: Serializer>(
+    cached_impulses: &HashMap<ContactKey, Vec<f32>>,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let mut entries: Vec<(&ContactKey, &Vec<f32>)> = cached_impulses.iter().collect();
+    entries.sort_by_key(|(key, _)| **key);
+    serializer.collect_seq(entries)
+}
+
+fn deserialize_cached_impulses<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<HashMap<ContactKey, Vec<f32>>, D::Error> {
+    let entries: Vec<(ContactKey, Vec<f32>)> = Vec::deserialize(deserializer)?;
+    Ok(entries.into_iter().collect())
+}
+
+impl ContactSolver {
+    pub fn new(info: SolverInfo, position_correction: PositionCorrectionInfo) -> Self {
+        Self {
+            info,
+            position_correction,
+            cached_impulses: HashMap::new(),
+        }
+    }
+    pub fn get_info(&self) -> SolverInfo {
+        self.info
+    }
+    pub fn set_info(&mut self, info: SolverInfo) {
+        self.info = info;
+    }
+    pub fn get_position_correction(&self) -> PositionCorrectionInfo {
+        self.position_correction
+    }
+    pub fn set_position_correction(&mut self, position_correction: PositionCorrectionInfo) {
+        self.position_correction = position_correction;
+    }
+    #[doc = "Forgets the cached impulses of every contact involving `id`"]
+    pub fn remove_body(&mut self, id: RBid) {
+        self.cached_impulses
+            .retain(|key, _| key.primary != id && key.target != ContactTarget::Body(id));
+    }
+
+    #[doc = "Resolves the velocities of `objects` so that no manifold is approaching. Positions are left untouched."]
+    pub fn solve(&mut self, objects: &mut [RigidBody], manifolds: &mut [ContactManifold], dt: f32) {
+        let resolved_indices = resolve_indices(objects, manifolds);
+
+        // Pre-step: effective masses and restitution targets, all from the velocities before warm starting.
+        for (manifold, resolved) in std::iter::zip(manifolds.iter_mut(), &resolved_indices) {
+            let Some((primary_idx, target_idx)) = resolved else {
+                continue;
+            };
+            let inverse_mass_sum = objects[*primary_idx].get_inverse_mass()
+                + target_idx.map_or(0., |idx| objects[idx].get_inverse_mass());
+            let relative_normal_velocity =
+                relative_velocity(objects, *primary_idx, *target_idx).dot(manifold.normal);
+            let cached = match self.info.warm_starting {
+                true => self.cached_impulses.get(&manifold.key),
+                false => None,
+            };
+            for (point_idx, point) in manifold.points.iter_mut().enumerate() {
+                point.normal_mass = if inverse_mass_sum > 0. {
+                    1. / inverse_mass_sum
+                } else {
+                    0.
+                };
+                point.velocity_bias = if relative_normal_velocity < -RESTITUTION_VELOCITY_THRESHOLD
+                {
+                    -COEFF_RESTITUTION * relative_normal_velocity
+                } else {
+                    0.
+                };
+                if self.position_correction.method == PositionCorrection::Baumgarte {
+                    point.velocity_bias +=
+                        self.position_correction.get_correction(point.penetration) / dt;
+                }
+                point.normal_impulse = cached
+                    .and_then(|impulses| impulses.get(point_idx).copied())
+                    .unwrap_or(0.);
+            }
+        }
+
+        for (manifold, resolved) in std::iter::zip(manifolds.iter(), &resolved_indices) {
+            let Some((primary_idx, target_idx)) = resolved else {
+                continue;
+            };
+            for point in &manifold.points {
+                apply_impulse(
+                    objects,
+                    *primary_idx,
+                    *target_idx,
+                    manifold.normal * point.normal_impulse,
+                );
+            }
+        }
+
+        for _ in 0..self.info.iterations {
+            for (manifold, resolved) in std::iter::zip(manifolds.iter_mut(), &resolved_indices) {
+                let Some((primary_idx, target_idx)) = resolved else {
+                    continue;
+                };
+                for point in manifold.points.iter_mut() {
+                    let relative_normal_velocity =
+                        relative_velocity(objects, *primary_idx, *target_idx).dot(manifold.normal);
+                    let impulse_change =
+                        point.normal_mass * (point.velocity_bias - relative_normal_velocity);
+                    let accumulated = (point.normal_impulse + impulse_change).max(0.);
+                    let impulse_change = accumulated - point.normal_impulse;
+                    point.normal_impulse = accumulated;
+                    apply_impulse(
+                        objects,
+                        *primary_idx,
+                        *target_idx,
+                        manifold.normal * impulse_change,
+                    );
+                }
+            }
+        }
+
+        self.cached_impulses = manifolds
+            .iter()
+            .map(|manifold| {
+                (
+                    manifold.key,
+                    manifold
+                        .points
+                        .iter()
+                        .map(|point| point.normal_impulse)
+                        .collect(),
+                )
+            })
+            .collect();
+    }
+
+    #[doc = "Split-impulse stage, run after positions are integrated. Pushes bodies apart along each manifold normal in proportion to their inverse masses."]
+    pub fn correct_positions(&self, objects: &mut [RigidBody], manifolds: &[ContactManifold]) {
+        if self.position_correction.method != PositionCorrection::SplitImpulse {
+            return;
+        }
+        let resolved_indices = resolve_indices(objects, manifolds);
+        for _ in 0..self.position_correction.iterations {
+            for (manifold, resolved) in std::iter::zip(manifolds, &resolved_indices) {
+                let Some((primary_idx, target_idx)) = resolved else {
+                    continue;
+                };
+                let primary = &objects[*primary_idx];
+                let (penetration, normal) = match (manifold.key.target, target_idx) {
+                    (ContactTarget::Body(_), Some(idx)) => {
+                        let target = &objects[*idx];
+                        let vector_between_com = target.get_position() - primary.get_position();
+                        let distance = vector_between_com.magnitude();
+                        let normal = if distance > f32::EPSILON {
+                            vector_between_com * (1. / distance)
+                        } else {
+                            manifold.normal
+                        };
+                        (
+                            primary.get_radius() + target.get_radius() - distance,
+                            normal,
+                        )
+                    }
+                    (ContactTarget::World(wall), _) => (
+                        wall.get_penetration(primary.get_position(), primary.get_radius()),
+                        manifold.normal,
+                    ),
+                    _ => continue,
+                };
+                let inverse_mass_sum = primary.get_inverse_mass()
+                    + target_idx.map_or(0., |idx| objects[idx].get_inverse_mass());
+                let correction = self.position_correction.get_correction(penetration);
+                if correction <= 0. || inverse_mass_sum <= 0. {
+                    continue;
+                }
+
+                let correction = normal * (correction / inverse_mass_sum);
+                let primary = &mut objects[*primary_idx];
+                let updated_position =
+                    primary.get_position() - correction * primary.get_inverse_mass();
+                primary.update_position(updated_position);
+                if let Some(idx) = target_idx {
+                    let target = &mut objects[*idx];
+                    let updated_position =
+                        target.get_position() + correction * target.get_inverse_mass();
+                    target.update_position(updated_position);
+                }
+            }
+        }
+    }
+}
+
+#[doc = "Maps each manifold to the indices of its primary and, unless it is a wall, its target in `objects`. Manifolds naming a body that is no longer in `objects` map to `None` and are skipped."]
+fn resolve_indices(
+    objects: &[RigidBody],
+    manifolds: &[ContactManifold],
+) -> Vec<Option<(usize, Option<usize>)>> {
+    let indices: HashMap<RBid, usize> = objects
+        .iter()
+        .enumerate()
+        .map(|(idx, obj)| (obj.get_id(), idx))
+        .collect();
+    manifolds
+        .iter()
+        .map(|manifold| {
+            let primary_idx = *indices.get(&manifold.key.primary)?;
+            let target_idx = match manifold.key.target {
+                ContactTarget::Body(id) => Some(*indices.get(&id)?),
+                ContactTarget::World(_) => None,
+            };
+            Some((primary_idx, target_idx))
+        })
+        .collect()
+}
+
+#[doc = "Velocity of the target relative to the primary. Walls are static."]
+fn relative_velocity(
+    objects: &[RigidBody],
+    primary_idx: usize,
+    target_idx: Option<usize>,
+) -> FVec2 {
+    let target_velocity = target_idx.map_or(FVec2::new(0., 0.), |idx| objects[idx].get_velocity());
+    target_velocity - objects[primary_idx].get_velocity()
+}
+
+#[doc = "Applies `impulse` to the target and its opposite to the primary"]
+fn apply_impulse(
+    objects: &mut [RigidBody],
+    primary_idx: usize,
+    target_idx: Option<usize>,
+    impulse: FVec2,
+) {
+    let primary = &mut objects[primary_idx];
+    let updated_velocity = primary.get_velocity() - impulse * primary.get_inverse_mass();
+    primary.update_velocity(updated_velocity);
+    if let Some(idx) = target_idx {
+        let target = &mut objects[idx];
+        let updated_velocity = target.get_velocity() + impulse * target.get_inverse_mass();
+        target.update_velocity(updated_velocity);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{ContactManifold, ContactSolver, SolverInfo};
+    use crate::{
+        physics::{
+            collision::{Collision, CollisionHandler, CollisionObjectType},
+            test_util::circle,
+        },
+        FVec2,
+    };
+
+    #[test]
+    fn check_head_on_collision_separates() {
+        let mut objects = vec![
+            circle(FVec2::new(-0.09, 0.), FVec2::new(1., 0.), 0),
+            circle(FVec2::new(0.09, 0.), FVec2::new(-1., 0.), 1),
+        ];
+        let collision = Collision::new(
+            CollisionObjectType::Object,
+            Some(objects[0].clone()),
+            Some(objects[1].clone()),
+        );
+        let mut manifolds = ContactManifold::from_collision(&collision);
+        let mut solver = ContactSolver::new(SolverInfo::default(), Default::default());
+        solver.solve(&mut objects, &mut manifolds, 1e-3);
+
+        let relative_velocity = objects[1].get_velocity() - objects[0].get_velocity();
+        assert!(relative_velocity.dot(manifolds[0].get_normal()) >= 0.);
+        // Equal masses, so momentum stays at zero.
+        assert!((objects[0].get_velocity() + objects[1].get_velocity()).magnitude() < 1e-5);
+    }
+
+    #[test]
+    fn check_resting_contact_impulse_is_warm_started() {
+        let mut solver = ContactSolver::new(SolverInfo::default(), Default::default());
+        let mut resting_impulse = 0.;
+        for _ in 0..2 {
+            let mut objects = vec![circle(FVec2::new(0., 0.905), FVec2::new(0., 0.03), 0)];
+            let mut manifolds = objects[0]
+                .check_world_collisions()
+                .iter()
+                .flat_map(ContactManifold::from_collision)
+                .collect::<Vec<_>>();
+            solver.solve(&mut objects, &mut manifolds, 1e-3);
+            assert!(objects[0].get_velocity().y <= 0.);
+            resting_impulse = manifolds[0].get_points()[0].get_normal_impulse();
+        }
+        assert!(resting_impulse > 0.);
+    }
+
+    #[test]
+    fn check_split_impulse_leaves_velocity() {
+        let mut objects = vec![
+            circle(FVec2::new(-0.05, 0.), FVec2::new(0., 0.), 0),
+            circle(FVec2::new(0.05, 0.), FVec2::new(0., 0.), 1),
+        ];
+        let collision = Collision::new(
+            CollisionObjectType::Object,
+            Some(objects[0].clone()),
+            Some(objects[1].clone()),
+        );
+        let manifolds = ContactManifold::from_collision(&collision);
+        let solver = ContactSolver::new(SolverInfo::default(), Default::default());
+        for _ in 0..20 {
+            solver.correct_positions(&mut objects, &manifolds);
+        }
+
+        let distance = (objects[1].get_position() - objects[0].get_position()).magnitude();
+        assert!(distance > 0.2 - 0.01 && distance <= 0.2);
+        assert_eq!(objects[0].get_velocity(), FVec2::new(0., 0.));
+    }
+
+    #[test]
+    fn check_manifold_of_despawned_body_is_skipped() {
+        let mut objects = vec![
+            circle(FVec2::new(-0.09, 0.), FVec2::new(1., 0.), 0),
+            circle(FVec2::new(0.09, 0.), FVec2::new(-1., 0.), 1),
+        ];
+        let collision = Collision::new(
+            CollisionObjectType::Object,
+            Some(objects[0].clone()),
+            Some(objects[1].clone()),
+        );
+        let mut manifolds = ContactManifold::from_collision(&collision);
+        objects.pop();
+        let mut solver = ContactSolver::new(SolverInfo::default(), Default::default());
+        solver.solve(&mut objects, &mut manifolds, 1e-3);
+        solver.correct_positions(&mut objects, &manifolds);
+
+        assert_eq!(objects[0].get_velocity(), FVec2::new(1., 0.));
+        assert_eq!(objects[0].get_position(), FVec2::new(-0.09, 0.));
+    }
+}
@@ -0,0 +1,44 @@
+use crate::FVec2;
+
+use super::{
+    circle::Circle,
+    rigidbody::{RBid, RigidBody},
+    scene::{Backend, Scene, SceneInfo},
+};
+
+#[doc = "Circle of radius 0.1 with the handle `RBid::new(index, 0)`, for tests that work on bodies without a scene"]
+pub fn circle(position: FVec2, velocity: FVec2, index: u32) -> RigidBody {
+    RigidBody::Circle_(
+        Circle {
+            radius: 0.1,
+            position,
+            velocity,
+            ..Default::default()
+        },
+        RBid::new(index, 0),
+    )
+}
+
+#[doc = "Circle of radius 0.1 on the x axis moving along it, to be added to a scene"]
+pub fn moving_circle(x: f32, vx: f32) -> RigidBody {
+    RigidBody::from(Circle {
+        radius: 0.1,
+        position: FVec2::new(x, 0.),
+        velocity: FVec2::new(vx, 0.),
+        ..Default::default()
+    })
+}
+
+#[doc = "Scene simulating `objects` on the CPU backend with default settings"]
+pub fn cpu_scene(objects: Vec<RigidBody>) -> Scene {
+    Scene::with_info(SceneInfo {
+        objects,
+        backend: Backend::Cpu,
+        ..Default::default()
+    })
+}
+
+#[doc = "Two circles heading towards each other on the CPU backend"]
+pub fn two_body_scene() -> Scene {
+    cpu_scene(vec![moving_circle(-0.5, 2.), moving_circle(0.5, -2.)])
+}
@@ -0,0 +1,156 @@
+use std::collections::VecDeque;
+
+use tracing::warn;
+
+use super::{
+    lib::{TIMELINE_KEYFRAME_INTERVAL, TIMELINE_MEMORY_BUDGET},
+    snapshot::SceneSnapshot,
+};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TimelineInfo {
+    #[doc = "Steps between keyframes. Seeking re-simulates at most this many steps."]
+    pub keyframe_interval: u32,
+    #[doc = "Bytes of encoded keyframes kept; the oldest are dropped past this"]
+    pub memory_budget: usize,
+}
+
+impl Default for TimelineInfo {
+    fn default() -> Self {
+        Self {
+            keyframe_interval: TIMELINE_KEYFRAME_INTERVAL,
+            memory_budget: TIMELINE_MEMORY_BUDGET,
+        }
+    }
+}
+
+#[doc = "Keyframe snapshots of a run, so any past step can be revisited by restoring the keyframe before it and stepping forward. Keyframes are stored in the binary snapshot encoding to keep the history small."]
+#[derive(Clone, Debug, Default)]
+pub struct Timeline {
+    info: TimelineInfo,
+    keyframes: VecDeque<(u64, Vec<u8>)>,
+    memory_used: usize,
+    head: u64,
+}
+
+impl Timeline {
+    pub fn new(info: TimelineInfo) -> Self {
+        Self {
+            info,
+            ..Default::default()
+        }
+    }
+    pub fn get_info(&self) -> TimelineInfo {
+        self.info
+    }
+    pub fn set_info(&mut self, info: TimelineInfo) {
+        self.info = info;
+        self.enforce_budget();
+    }
+    #[doc = "Earliest step that can still be revisited"]
+    pub fn get_start(&self) -> Option<u64> {
+        self.keyframes.front().map(|(step, _)| *step)
+    }
+    #[doc = "Furthest step the run has reached. Steps after a rewind stay reachable until the scene carries on from the earlier point."]
+    pub fn get_head(&self) -> u64 {
+        self.head
+    }
+    pub fn get_memory_used(&self) -> usize {
+        self.memory_used
+    }
+    pub fn is_due(&self, step: u64) -> bool {
+        self.info.keyframe_interval > 0 && step.is_multiple_of(self.info.keyframe_interval as u64)
+    }
+
+    #[doc = "Marks `step` as the newest step of the run, dropping the keyframes of any future it branched off from"]
+    pub fn advance_head(&mut self, step: u64) {
+        while self
+            .keyframes
+            .back()
+            .is_some_and(|(keyframe_step, _)| *keyframe_step > step)
+        {
+            let (_, bytes) = self.keyframes.pop_back().unwrap();
+            self.memory_used -= bytes.len();
+        }
+        self.head = step;
+    }
+
+    pub fn record(&mut self, snapshot: &SceneSnapshot) {
+        let step = snapshot.get_step_count();
+        if self
+            .keyframes
+            .back()
+            .is_some_and(|(keyframe_step, _)| *keyframe_step >= step)
+        {
+            return;
+        }
+        match snapshot.to_bytes() {
+            Ok(bytes) => {
+                self.memory_used += bytes.len();
+                self.keyframes.push_back((step, bytes));
+                self.enforce_budget();
+            }
+            Err(e) => warn!("failed to encode a timeline keyframe: {}", e),
+        }
+    }
+
+    pub fn get_keyframe_step(&self, step: u64) -> Option<u64> {
+        self.keyframes
+            .iter()
+            .rev()
+            .map(|(keyframe_step, _)| *keyframe_step)
+            .find(|keyframe_step| *keyframe_step <= step)
+    }
+
+    #[doc = "Latest keyframe at or before `step`"]
+    pub fn get_keyframe(&self, step: u64) -> Option<SceneSnapshot> {
+        let (_, bytes) = self
+            .keyframes
+            .iter()
+            .rev()
+            .find(|(keyframe_step, _)| *keyframe_step <= step)?;
+        SceneSnapshot::from_bytes(bytes)
+            .map_err(|e| warn!("failed to decode a timeline keyframe: {}", e))
+            .ok()
+    }
+
+    // NOTE: The newest keyframe is always kept, even over budget, so the timeline is never empty
+    // once recording has started.
+    fn enforce_budget(&mut self) {
+        while self.memory_used > self.info.memory_budget && self.keyframes.len() > 1 {
+            let (_, bytes) = self.keyframes.pop_front().unwrap();
+            self.memory_used -= bytes.len();
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::physics::test_util::two_body_scene;
+
+    #[test]
+    fn check_seek_reproduces_the_run() {
+        let mut scene = two_body_scene();
+        let mut history = vec![scene.bodies().to_vec()];
+        for _ in 0..500 {
+            scene.step();
+            history.push(scene.bodies().to_vec());
+        }
+
+        scene.seek(137);
+        assert!(scene.is_paused());
+        assert_eq!(scene.get_step_count(), 137);
+        assert_eq!(scene.bodies(), &history[137][..]);
+        scene.seek(401);
+        assert_eq!(scene.bodies(), &history[401][..]);
+        assert_eq!(scene.get_timeline().get_head(), 500);
+
+        // NOTE: Carrying on from a past step drops the future it branched off from.
+        scene.seek(100);
+        scene.resume();
+        scene.step();
+        assert_eq!(scene.get_timeline().get_head(), 101);
+        scene.seek(400);
+        assert_eq!(scene.get_step_count(), 101);
+    }
+}
@@ -0,0 +1,171 @@
+use crate::{
+    physics::{rigidbody::RBid, scene::Scene},
+    FVec2,
+};
+
+use super::shaders::vs::ViewConstants;
+
+pub const CAMERA_MIN_ZOOM: f32 = 0.05;
+pub const CAMERA_MAX_ZOOM: f32 = 50.;
+#[doc = "Zoom factor applied per line scrolled"]
+pub const CAMERA_ZOOM_STEP: f32 = 1.1;
+
+#[doc = "Maps world coordinates to normalized device coordinates. `center` is the world point in the middle of the window, and the shorter side of the window spans `2 / zoom` world units, so the default camera shows the world square [-1,1]² whatever the window's shape. y points down in both spaces."]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Camera {
+    center: FVec2,
+    zoom: f32,
+    #[doc = "Width over height of the window"]
+    aspect_ratio: f32,
+    #[doc = "Body kept in the middle of the window"]
+    follow: Option<RBid>,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self {
+            center: FVec2::new(0., 0.),
+            zoom: 1.,
+            aspect_ratio: 1.,
+            follow: None,
+        }
+    }
+}
+
+impl Camera {
+    #[doc = "Camera showing the whole world box from `min` to `max`, with more around it along the window's longer side"]
+    pub fn framing(min: FVec2, max: FVec2) -> Self {
+        let size = max - min;
+        Self {
+            center: (min + max) * 0.5,
+            zoom: (2. / size.x.max(size.y)).clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM),
+            ..Default::default()
+        }
+    }
+    #[doc = "Back to the default view, keeping the aspect ratio"]
+    pub fn reset(&mut self) {
+        *self = Self {
+            aspect_ratio: self.aspect_ratio,
+            ..Default::default()
+        };
+    }
+    pub fn get_center(&self) -> FVec2 {
+        self.center
+    }
+    pub fn set_center(&mut self, center: FVec2) {
+        self.center = center;
+    }
+    pub fn get_zoom(&self) -> f32 {
+        self.zoom
+    }
+    pub fn set_zoom(&mut self, zoom: f32) {
+        self.zoom = zoom.clamp(CAMERA_MIN_ZOOM, CAMERA_MAX_ZOOM);
+    }
+    pub fn get_aspect_ratio(&self) -> f32 {
+        self.aspect_ratio
+    }
+    pub fn set_aspect_ratio(&mut self, aspect_ratio: f32) {
+        self.aspect_ratio = aspect_ratio;
+    }
+    pub fn get_follow(&self) -> Option<RBid> {
+        self.follow
+    }
+    pub fn set_follow(&mut self, follow: Option<RBid>) {
+        self.follow = follow;
+    }
+
+    #[doc = "World units covered by one pixel, in a window whose shorter side is `shorter_side` pixels long"]
+    pub fn get_pixel_size(&self, shorter_side: u32) -> f32 {
+        2. / (self.zoom * shorter_side.max(1) as f32)
+    }
+
+    #[doc = "NDC units per world unit along each axis"]
+    fn get_scale(&self) -> FVec2 {
+        match self.aspect_ratio >= 1. {
+            true => FVec2::new(self.zoom / self.aspect_ratio, self.zoom),
+            false => FVec2::new(self.zoom, self.zoom * self.aspect_ratio),
+        }
+    }
+    #[doc = "World distance covered by `ndc_delta`"]
+    fn ndc_to_world_delta(&self, ndc_delta: FVec2) -> FVec2 {
+        let scale = self.get_scale();
+        FVec2::new(ndc_delta.x / scale.x, ndc_delta.y / scale.y)
+    }
+
+    pub fn world_to_ndc(&self, point: FVec2) -> FVec2 {
+        let scale = self.get_scale();
+        let offset = point - self.center;
+        FVec2::new(offset.x * scale.x, offset.y * scale.y)
+    }
+    pub fn ndc_to_world(&self, point: FVec2) -> FVec2 {
+        self.ndc_to_world_delta(point) + self.center
+    }
+
+    #[doc = "Zooms by `factor`, keeping the world point under `ndc` in place"]
+    pub fn zoom_at(&mut self, ndc: FVec2, factor: f32) {
+        let anchor = self.ndc_to_world(ndc);
+        self.set_zoom(self.zoom * factor);
+        self.center = anchor - self.ndc_to_world_delta(ndc);
+    }
+
+    #[doc = "Moves the view by `ndc_delta`, so the world follows the cursor while dragging. Stops following a body."]
+    pub fn pan_by(&mut self, ndc_delta: FVec2) {
+        self.center = self.center - self.ndc_to_world_delta(ndc_delta);
+        self.follow = None;
+    }
+
+    #[doc = "Centres on the followed body, and stops following it once it is despawned"]
+    pub fn update(&mut self, scene: &Scene) {
+        let Some(id) = self.follow else {
+            return;
+        };
+        match scene.body(id) {
+            Some(body) => self.center = body.get_position(),
+            None => self.follow = None,
+        }
+    }
+
+    #[doc = "Column-major view-projection matrix for the vertex shader"]
+    pub(crate) fn get_view_constants(&self) -> ViewConstants {
+        let scale = self.get_scale();
+        let offset = FVec2::new(-self.center.x * scale.x, -self.center.y * scale.y);
+        ViewConstants {
+            view_projection: [
+                [scale.x, 0., 0., 0.],
+                [0., scale.y, 0., 0.],
+                [0., 0., 1., 0.],
+                [offset.x, offset.y, 0., 1.],
+            ],
+        }
+    }
+
+    #[doc = "Constants drawing in normalized device coordinates, for overlays"]
+    pub(crate) fn get_screen_view_constants() -> ViewConstants {
+        Camera::default().get_view_constants()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::FVec2;
+
+    use super::Camera;
+
+    #[test]
+    fn check_zoom_keeps_cursor_point_fixed() {
+        let mut camera = Camera::framing(FVec2::new(-4., -2.), FVec2::new(4., 2.));
+        assert_eq!(camera.get_zoom(), 0.25);
+        camera.set_aspect_ratio(16. / 9.);
+        assert!((camera.world_to_ndc(FVec2::new(0., 2.)).y - 0.5).abs() < 1e-5);
+
+        let cursor = FVec2::new(0.5, -0.25);
+        let before = camera.ndc_to_world(cursor);
+        camera.zoom_at(cursor, 3.);
+        let after = camera.ndc_to_world(cursor);
+        assert!((before - after).magnitude() < 1e-5);
+
+        camera.pan_by(FVec2::new(0.1, 0.));
+        let ndc = camera.world_to_ndc(after);
+        assert!((ndc - (cursor + FVec2::new(0.1, 0.))).magnitude() < 1e-5);
+    }
+}
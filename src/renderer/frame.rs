@@ -0,0 +1,238 @@
+use std::sync::Arc;
+
+use vulkano::{
+    buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer},
+    command_buffer::{allocator::StandardCommandBufferAllocator, PrimaryAutoCommandBuffer},
+    device::Queue,
+    memory::allocator::{
+        AllocationCreateInfo, FreeListAllocator, GenericMemoryAllocator, MemoryTypeFilter,
+    },
+    pipeline::GraphicsPipeline,
+    render_pass::Framebuffer,
+};
+
+use crate::physics::{rigidbody::RigidBody, scene::BodyStyle};
+
+use super::{
+    vk_core::{CustomVertex, RuntimeBuffers, UnitVertex},
+    vk_primitives::get_render_command_buffer,
+};
+
+#[doc = "Vertices the first vertex buffer of a frame ring holds before it has to grow"]
+const INITIAL_VERTEX_CAPACITY: usize = 1 << 14;
+#[doc = "Bodies the first instance buffers of a frame ring hold before they have to grow"]
+const INITIAL_INSTANCE_CAPACITY: usize = 1 << 10;
+
+#[doc = "What a recorded command buffer draws. The vertices themselves are not part of it, so it stays valid while bodies move."]
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct FrameDraw {
+    #[doc = "Bodies drawn from `BodyInstances`, before any vertex"]
+    pub instances: Option<InstanceDraw>,
+    #[doc = "Vertices drawn through the camera; the rest are overlays"]
+    pub world_vertex_count: u32,
+    pub vertex_count: u32,
+    pub view_projection: [[f32; 4]; 4],
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct InstanceDraw {
+    pub count: u32,
+    pub style: BodyStyle,
+    #[doc = "World units covered by one pixel, which SDF circles smooth their edges over"]
+    pub pixel_size: f32,
+    #[doc = "Drawn from the compute shader's buffers rather than the ones uploaded with the frame"]
+    pub is_resident: bool,
+}
+
+#[doc = "Per-body position, radius and color, laid out as in `RuntimeBuffers`"]
+#[derive(Clone)]
+pub struct InstanceBuffers {
+    pub positions: Subbuffer<[[f32; 2]]>,
+    #[doc = "Radius, followed by the GPU flags"]
+    pub radii: Subbuffer<[[f32; 2]]>,
+    pub colors: Subbuffer<[[u8; 4]]>,
+}
+
+impl From<&RuntimeBuffers> for InstanceBuffers {
+    fn from(runtime_buffers: &RuntimeBuffers) -> Self {
+        Self {
+            positions: runtime_buffers.objects_positions.clone(),
+            radii: runtime_buffers.objects_radii.clone(),
+            colors: runtime_buffers.objects_colors.clone(),
+        }
+    }
+}
+
+#[doc = "Pipeline, shape and buffers drawing every body as an instance of one unit shape"]
+#[derive(Clone)]
+pub struct BodyInstances {
+    pub pipeline: Arc<GraphicsPipeline>,
+    #[doc = "Triangles of a polygon of radius 1, or of the square [-1,1]² for SDF circles"]
+    pub unit_shape: Subbuffer<[UnitVertex]>,
+    pub buffers: InstanceBuffers,
+}
+
+#[doc = "Vertex and instance buffers of one swapchain image, and the command buffer last recorded against them"]
+struct FrameSlot {
+    vertex_buffer: Subbuffer<[CustomVertex]>,
+    instance_buffers: Option<InstanceBuffers>,
+    command_buffer: Option<(FrameDraw, Arc<PrimaryAutoCommandBuffer>)>,
+}
+
+#[doc = "Persistent per-image vertex buffers and command buffers. Each frame the vertices are gathered into one reused `Vec` and uploaded with a single copy into the slot of the acquired image, which the image's fence guarantees the GPU is done with. Bodies drawn as instances from the CPU are uploaded the same way. Command buffers are only re-recorded when what they draw changes."]
+pub struct FrameRing {
+    memory_allocator: Arc<GenericMemoryAllocator<FreeListAllocator>>,
+    command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+    queue: Arc<Queue>,
+    slots: Vec<FrameSlot>,
+    vertices: Vec<CustomVertex>,
+    instance_positions: Vec<[f32; 2]>,
+    instance_radii: Vec<[f32; 2]>,
+    instance_colors: Vec<[u8; 4]>,
+}
+
+impl FrameRing {
+    pub fn new(
+        memory_allocator: Arc<GenericMemoryAllocator<FreeListAllocator>>,
+        command_buffer_allocator: Arc<StandardCommandBufferAllocator>,
+        queue: Arc<Queue>,
+    ) -> Self {
+        Self {
+            memory_allocator,
+            command_buffer_allocator,
+            queue,
+            slots: vec![],
+            vertices: vec![],
+            instance_positions: vec![],
+            instance_radii: vec![],
+            instance_colors: vec![],
+        }
+    }
+
+    #[doc = "Empties the vertex list for the next frame, keeping its allocation"]
+    pub fn begin_frame(&mut self) -> &mut Vec<CustomVertex> {
+        self.vertices.clear();
+        &mut self.vertices
+    }
+
+    #[doc = "Replaces the bodies uploaded as instances this frame"]
+    pub fn set_instances(&mut self, bodies: &[RigidBody]) {
+        self.instance_positions.clear();
+        self.instance_radii.clear();
+        self.instance_colors.clear();
+        for body in bodies {
+            self.instance_positions.push(body.get_position().as_array());
+            self.instance_radii
+                .push([body.get_radius(), body.get_gpu_flags() as f32]);
+            self.instance_colors.push(body.get_color().to_array());
+        }
+    }
+
+    #[doc = "Drops every recorded command buffer, e.g. after the framebuffers, pipelines or instance buffers were recreated"]
+    pub fn invalidate(&mut self) {
+        for slot in self.slots.iter_mut() {
+            slot.command_buffer = None;
+        }
+    }
+
+    fn create_buffer<T: BufferContents>(&self, capacity: usize) -> Subbuffer<[T]> {
+        Buffer::new_slice(
+            self.memory_allocator.clone(),
+            BufferCreateInfo {
+                usage: BufferUsage::VERTEX_BUFFER,
+                ..Default::default()
+            },
+            AllocationCreateInfo {
+                memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                    | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+                ..Default::default()
+            },
+            capacity as u64,
+        )
+        .expect("frame ring: could not allocate a vertex buffer")
+    }
+
+    fn create_instance_buffers(&self, capacity: usize) -> InstanceBuffers {
+        InstanceBuffers {
+            positions: self.create_buffer(capacity),
+            radii: self.create_buffer(capacity),
+            colors: self.create_buffer(capacity),
+        }
+    }
+
+    #[doc = "Copies this frame's vertices and instances into the slot of `image_i`, growing its buffers if they no longer fit. The GPU must be done with the slot."]
+    pub fn upload(&mut self, image_i: usize) -> anyhow::Result<()> {
+        while self.slots.len() <= image_i {
+            let vertex_buffer = self.create_buffer(INITIAL_VERTEX_CAPACITY);
+            self.slots.push(FrameSlot {
+                vertex_buffer,
+                instance_buffers: None,
+                command_buffer: None,
+            });
+        }
+        if self.slots[image_i].vertex_buffer.len() < self.vertices.len() as u64 {
+            self.slots[image_i].vertex_buffer =
+                self.create_buffer(self.vertices.len().next_power_of_two());
+            self.slots[image_i].command_buffer = None;
+        }
+        let mut writer = self.slots[image_i].vertex_buffer.write()?;
+        writer[..self.vertices.len()].clone_from_slice(&self.vertices);
+        drop(writer);
+
+        let instance_count = self.instance_positions.len();
+        if instance_count == 0 {
+            return Ok(());
+        }
+        let capacity = self.slots[image_i]
+            .instance_buffers
+            .as_ref()
+            .map_or(0, |buffers| buffers.positions.len() as usize);
+        if capacity < instance_count {
+            let capacity = instance_count
+                .next_power_of_two()
+                .max(INITIAL_INSTANCE_CAPACITY);
+            self.slots[image_i].instance_buffers = Some(self.create_instance_buffers(capacity));
+            self.slots[image_i].command_buffer = None;
+        }
+        let Some(buffers) = self.slots[image_i].instance_buffers.as_ref() else {
+            return Ok(());
+        };
+        buffers.positions.write()?[..instance_count].copy_from_slice(&self.instance_positions);
+        buffers.radii.write()?[..instance_count].copy_from_slice(&self.instance_radii);
+        buffers.colors.write()?[..instance_count].copy_from_slice(&self.instance_colors);
+        Ok(())
+    }
+
+    #[doc = "Instances uploaded into the slot of `image_i`, if any ever were"]
+    pub fn get_instance_buffers(&self, image_i: usize) -> Option<InstanceBuffers> {
+        self.slots.get(image_i)?.instance_buffers.clone()
+    }
+
+    #[doc = "Command buffer drawing `instances` and the slot of `image_i` as described by `draw`, recorded again only if it changed"]
+    pub fn get_command_buffer(
+        &mut self,
+        image_i: usize,
+        draw: FrameDraw,
+        pipeline: &Arc<GraphicsPipeline>,
+        framebuffer: &Arc<Framebuffer>,
+        instances: Option<&BodyInstances>,
+    ) -> anyhow::Result<Arc<PrimaryAutoCommandBuffer>> {
+        let slot = &mut self.slots[image_i];
+        if let Some((recorded_draw, command_buffer)) = &slot.command_buffer {
+            if *recorded_draw == draw {
+                return Ok(command_buffer.clone());
+            }
+        }
+        let command_buffer = get_render_command_buffer(
+            &self.command_buffer_allocator,
+            &self.queue,
+            pipeline,
+            framebuffer,
+            &slot.vertex_buffer,
+            instances,
+            draw,
+        )?;
+        slot.command_buffer = Some((draw, command_buffer.clone()));
+        Ok(command_buffer)
+    }
+}
@@ -1,4 +1,8 @@
+pub mod camera;
+pub mod frame;
+pub mod overlay;
 pub(crate) mod shaders;
+pub mod text;
 pub mod vk_core;
 pub mod vk_primitives;
 pub mod vk_procedural_functions;
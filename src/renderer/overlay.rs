@@ -0,0 +1,205 @@
+use ecolor::Color32;
+
+use crate::FVec2;
+
+use crate::core::keymap::{Action, Keymap};
+
+use super::{
+    text::{generate_text_triangles, GLYPH_ADVANCE, GLYPH_HEIGHT},
+    vk_core::CustomVertex,
+    vk_procedural_functions::{
+        generate_line_triangles, generate_rectangle_triangles, PolygonMethods,
+    },
+};
+
+const SLIDER_LEFT: f32 = -0.9;
+const SLIDER_RIGHT: f32 = 0.9;
+#[doc = "Vertical centre of the slider, near the bottom of the window (y points down)"]
+const SLIDER_Y: f32 = 0.93;
+const SLIDER_THICKNESS: f32 = 0.015;
+const SLIDER_MARKER_HEIGHT: f32 = 0.06;
+const SLIDER_MARKER_WIDTH: f32 = 0.01;
+#[doc = "Extra height around the bar that still counts as a click on it"]
+const SLIDER_GRAB_MARGIN: f32 = 0.03;
+
+#[doc = "Timeline bar along the bottom of the window. The track spans the steps that can be revisited and the marker shows the current one. Positions are in normalized device coordinates."]
+pub struct TimelineSlider {
+    pub start: u64,
+    pub head: u64,
+    pub current: u64,
+}
+
+impl TimelineSlider {
+    fn fraction(&self, step: u64) -> f32 {
+        match self.head > self.start {
+            true => {
+                (step.clamp(self.start, self.head) - self.start) as f32
+                    / (self.head - self.start) as f32
+            }
+            false => 1.,
+        }
+    }
+
+    pub fn to_vertices(&self) -> Vec<CustomVertex> {
+        let left = FVec2::new(SLIDER_LEFT, SLIDER_Y);
+        let right = FVec2::new(SLIDER_RIGHT, SLIDER_Y);
+        let marker_x = SLIDER_LEFT + (SLIDER_RIGHT - SLIDER_LEFT) * self.fraction(self.current);
+        let marker = FVec2::new(marker_x, SLIDER_Y);
+        let marker_offset = FVec2::new(0., SLIDER_MARKER_HEIGHT / 2.);
+
+        [
+            generate_line_triangles(left, right, SLIDER_THICKNESS, Color32::DARK_GRAY),
+            generate_line_triangles(left, marker, SLIDER_THICKNESS, Color32::LIGHT_BLUE),
+            generate_line_triangles(
+                marker - marker_offset,
+                marker + marker_offset,
+                SLIDER_MARKER_WIDTH,
+                Color32::WHITE,
+            ),
+        ]
+        .concat()
+        .destructure_into_list()
+    }
+
+    #[doc = "Step under `cursor`, if it is over the bar"]
+    pub fn step_at(&self, cursor: FVec2) -> Option<u64> {
+        let half_height = SLIDER_MARKER_HEIGHT / 2. + SLIDER_GRAB_MARGIN;
+        if (cursor.y - SLIDER_Y).abs() > half_height
+            || cursor.x < SLIDER_LEFT - SLIDER_GRAB_MARGIN
+            || cursor.x > SLIDER_RIGHT + SLIDER_GRAB_MARGIN
+        {
+            return None;
+        }
+        Some(self.step_along(cursor.x))
+    }
+
+    #[doc = "Step at horizontal position `x`, clamped to the ends of the bar. Used while dragging, when the cursor may drift off the bar."]
+    pub fn step_along(&self, x: f32) -> u64 {
+        let fraction = ((x - SLIDER_LEFT) / (SLIDER_RIGHT - SLIDER_LEFT)).clamp(0., 1.);
+        self.start + ((self.head - self.start) as f32 * fraction).round() as u64
+    }
+}
+
+const INDICATOR_LEFT: f32 = -0.95;
+const INDICATOR_RIGHT: f32 = -0.6;
+#[doc = "Vertical centre of the time scale indicator, near the top of the window"]
+const INDICATOR_Y: f32 = -0.93;
+const INDICATOR_THICKNESS: f32 = 0.015;
+const INDICATOR_TICK_HEIGHT: f32 = 0.04;
+
+#[doc = "Gauge in the top-left corner showing the playback speed on a log scale, from `min` at the left end to `max` at the right, with a tick at 1x. Blue is slow motion and orange is fast-forward."]
+pub struct TimeScaleIndicator {
+    pub time_scale: f32,
+    pub min: f32,
+    pub max: f32,
+}
+
+impl TimeScaleIndicator {
+    fn x_of(&self, time_scale: f32) -> f32 {
+        let fraction = (time_scale.ln() - self.min.ln()) / (self.max.ln() - self.min.ln());
+        INDICATOR_LEFT + (INDICATOR_RIGHT - INDICATOR_LEFT) * fraction.clamp(0., 1.)
+    }
+
+    pub fn to_vertices(&self) -> Vec<CustomVertex> {
+        let left = FVec2::new(INDICATOR_LEFT, INDICATOR_Y);
+        let right = FVec2::new(INDICATOR_RIGHT, INDICATOR_Y);
+        let level = FVec2::new(self.x_of(self.time_scale), INDICATOR_Y);
+        let tick = FVec2::new(self.x_of(1.), INDICATOR_Y);
+        let tick_offset = FVec2::new(0., INDICATOR_TICK_HEIGHT / 2.);
+        let level_color = match self.time_scale < 1. {
+            true => Color32::LIGHT_BLUE,
+            false => Color32::ORANGE,
+        };
+
+        [
+            generate_line_triangles(left, right, INDICATOR_THICKNESS, Color32::DARK_GRAY),
+            generate_line_triangles(left, level, INDICATOR_THICKNESS, level_color),
+            generate_line_triangles(
+                tick - tick_offset,
+                tick + tick_offset,
+                SLIDER_MARKER_WIDTH,
+                Color32::WHITE,
+            ),
+        ]
+        .concat()
+        .destructure_into_list()
+    }
+}
+
+const HELP_LEFT: f32 = -0.9;
+const HELP_TOP: f32 = -0.8;
+const HELP_PIXEL_SIZE: f32 = 0.006;
+const HELP_LINE_SPACING: f32 = 1.6;
+const HELP_PADDING: f32 = 0.03;
+#[doc = "Width of the key column, in characters"]
+const HELP_KEY_COLUMN: usize = 16;
+
+#[doc = "Panel listing every bound action next to the keys that trigger it. `step_batch` is the number of steps the step-N control advances by, and `aspect_ratio` is the window's width over its height, so the text keeps its shape."]
+pub struct HelpOverlay {
+    pub lines: Vec<(String, String)>,
+    pub aspect_ratio: f32,
+}
+
+impl HelpOverlay {
+    pub fn from_keymap(keymap: &Keymap, step_batch: u32, aspect_ratio: f32) -> Self {
+        let lines = keymap
+            .get_bindings()
+            .iter()
+            .filter(|(_, keys)| !keys.is_empty())
+            .map(|(action, keys)| {
+                let keys = keys
+                    .iter()
+                    .map(|key| format!("{:?}", key).to_uppercase())
+                    .collect::<Vec<_>>()
+                    .join("/");
+                let description = match action {
+                    Action::StepBatch => format!("step {}", step_batch),
+                    _ => action.get_description().to_owned(),
+                };
+                (keys, description)
+            })
+            .collect();
+        Self {
+            lines,
+            aspect_ratio,
+        }
+    }
+
+    pub fn to_vertices(&self) -> Vec<CustomVertex> {
+        let pixel_size = FVec2::new(HELP_PIXEL_SIZE / self.aspect_ratio, HELP_PIXEL_SIZE);
+        let line_height = GLYPH_HEIGHT as f32 * pixel_size.y * HELP_LINE_SPACING;
+        let column_width = (GLYPH_ADVANCE * HELP_KEY_COLUMN) as f32 * pixel_size.x;
+        let widest = self
+            .lines
+            .iter()
+            .map(|(_, description)| description.chars().count())
+            .max()
+            .unwrap_or(0);
+        let width = column_width + (GLYPH_ADVANCE * widest) as f32 * pixel_size.x;
+        let height = line_height * self.lines.len() as f32;
+
+        let padding = FVec2::new(HELP_PADDING / self.aspect_ratio, HELP_PADDING);
+        let top_left = FVec2::new(HELP_LEFT, HELP_TOP);
+        let mut triangles = generate_rectangle_triangles(
+            top_left - padding,
+            top_left + FVec2::new(width, height) + padding,
+            Color32::from_rgba_premultiplied(20, 20, 20, 255),
+        );
+        for (i, (keys, description)) in self.lines.iter().enumerate() {
+            let origin = top_left + FVec2::new(0., line_height * i as f32);
+            triangles.extend(generate_text_triangles(
+                keys,
+                origin,
+                pixel_size,
+                Color32::LIGHT_BLUE,
+            ));
+            triangles.extend(generate_text_triangles(
+                description,
+                origin + FVec2::new(column_width, 0.),
+                pixel_size,
+                Color32::WHITE,
+            ));
+        }
+        triangles.destructure_into_list()
+    }
+}
@@ -6,19 +6,99 @@ pub mod vs {
 
             layout(location = 0) in vec4 color;
             layout(location = 1) in vec2 position_in;
-            // layout(push_constant) uniform PolygonConstants {
-            //     float radius;
-            // };
+            // Maps world coordinates to clip space. Overlays are drawn with the identity.
+            layout(push_constant) uniform ViewConstants {
+                mat4 view_projection;
+            };
 
             layout(location = 0) out vec4 color_out;
             layout(location = 1) out vec2 position_out;
-            // layout(location = 2) out float radius_out;
+            layout(location = 2) out float radius_out;
+            layout(location = 3) out vec2 edge_out;
+            layout(location = 4) out vec4 outline_color_out;
            
             void main() {
                 color_out = color;
-                // radius_out = radius;
+                // A radius of 0 fills triangles flat; only circles evaluate a distance.
+                radius_out = 0.0;
+                edge_out = vec2(0.0);
+                outline_color_out = vec4(0.0);
                 position_out = position_in;
-                gl_Position = vec4(position_in, 0.0, 1.0);
+                gl_Position = view_projection * vec4(position_in, 0.0, 1.0);
+            }
+        ",
+    }
+}
+pub mod instance_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            // One vertex of a polygon of radius 1 around the origin.
+            layout(location = 0) in vec2 offset;
+            // Per body, read straight from the compute shader's storage buffers.
+            layout(location = 1) in vec2 instance_position;
+            layout(location = 2) in vec2 instance_radius;
+            layout(location = 3) in vec4 instance_color;
+            layout(push_constant) uniform ViewConstants {
+                mat4 view_projection;
+            };
+
+            layout(location = 0) out vec4 color_out;
+            layout(location = 1) out vec2 position_out;
+            layout(location = 2) out float radius_out;
+            layout(location = 3) out vec2 edge_out;
+            layout(location = 4) out vec4 outline_color_out;
+
+            void main() {
+                vec2 position = instance_position + offset * instance_radius.x;
+                color_out = instance_color;
+                radius_out = 0.0;
+                edge_out = vec2(0.0);
+                outline_color_out = vec4(0.0);
+                position_out = position;
+                gl_Position = view_projection * vec4(position, 0.0, 1.0);
+            }
+        ",
+    }
+}
+pub mod circle_vs {
+    vulkano_shaders::shader! {
+        ty: "vertex",
+        src: r"
+            #version 460
+
+            // One corner of the square [-1,1]², stretched over each body.
+            layout(location = 0) in vec2 offset;
+            layout(location = 1) in vec2 instance_position;
+            layout(location = 2) in vec2 instance_radius;
+            layout(location = 3) in vec4 instance_color;
+            layout(push_constant) uniform CircleConstants {
+                mat4 view_projection;
+                vec4 outline_color;
+                // In pixels; 0 draws no outline.
+                float outline_width;
+                // World units covered by one pixel.
+                float pixel_size;
+            };
+
+            layout(location = 0) out vec4 color_out;
+            layout(location = 1) out vec2 position_out;
+            layout(location = 2) out float radius_out;
+            layout(location = 3) out vec2 edge_out;
+            layout(location = 4) out vec4 outline_color_out;
+
+            void main() {
+                float radius = instance_radius.x;
+                // The square reaches a pixel past the circle, so its smoothed edge is not cut off.
+                vec2 local = offset * (radius + pixel_size);
+                color_out = instance_color;
+                position_out = local;
+                radius_out = radius;
+                edge_out = vec2(outline_width * pixel_size, pixel_size);
+                outline_color_out = outline_color;
+                gl_Position = view_projection * vec4(instance_position + local, 0.0, 1.0);
             }
         ",
     }
@@ -30,7 +110,12 @@ pub mod fs {
             #version 460
 
             layout(location = 0) in vec4 color;
+            // World position, or the offset from the centre for circles.
             layout(location = 1) in vec2 pos;
+            layout(location = 2) in float radius;
+            // Outline width and edge smoothing width, in world units.
+            layout(location = 3) in vec2 edge;
+            layout(location = 4) in vec4 outline_color;
             layout(location = 0) out vec4 f_color;
 
             // layout(set = 0, binding = 0) uniform sampler s;
@@ -39,7 +124,24 @@ pub mod fs {
             void main() {
                 // vec2 texture_coords = gl_FragCoord.xy;
                 // f_color = texture(sampler2D(tex, s), texture_coord);
-                f_color = color;
+                if (radius <= 0.0) {
+                    f_color = color;
+                    return;
+                }
+                // Signed distance to the circle, negative inside. Coverage fades over one pixel
+                // across the edge, so it stays smooth at any zoom.
+                float signed_distance = length(pos) - radius;
+                float half_smoothing = 0.5 * edge.y;
+                float coverage = 1.0 - smoothstep(-half_smoothing, half_smoothing, signed_distance);
+                if (coverage <= 0.0) {
+                    discard;
+                }
+                vec4 fill = color;
+                if (edge.x > 0.0) {
+                    float outline = smoothstep(-edge.x - half_smoothing, -edge.x + half_smoothing, signed_distance);
+                    fill = mix(color, outline_color, outline);
+                }
+                f_color = vec4(fill.rgb, fill.a * coverage);
             }
         ",
     }
@@ -55,8 +157,44 @@ pub mod update_cs {
                 float gravity;
                 float dt;
                 uint num_objects;
+                float slop;
+                float correction_factor;
+                uint num_constraints;
+                float ccd_threshold;
+                uint num_never_collide;
+                uint position_correction_method;
+            };
+
+            const float PI = 3.14159265;
+            const uint CONSTRAINT_DISTANCE = 0;
+            const uint CONSTRAINT_SPRING = 1;
+            const uint CONSTRAINT_ROPE = 2;
+            const uint FLAG_BULLET = 1;
+            const uint FLAG_SENSOR = 2;
+            const uint WORLD_COLLISION_CATEGORY = 1u << 31;
+            const uint POSITION_CORRECTION_NONE = 0;
+            const uint POSITION_CORRECTION_BAUMGARTE = 1;
+            const uint POSITION_CORRECTION_SPLIT_IMPULSE = 2;
+
+            // Matches `GpuConstraint`. `body_b` is -1 when the constraint is anchored to `anchor` in the world.
+            struct Constraint {
+                uint kind;
+                uint body_a;
+                int body_b;
+                float params[3];
+                vec2 anchor;
+            };
+
+            // Matches `GpuCollisionEvent`. A negative `body_b` is the wall `-1 - body_b`: -x, +x, -y, +y.
+            struct CollisionEvent {
+                uint body_a;
+                int body_b;
+                vec2 point;
+                vec2 normal;
+                float impulse;
             };
             
+            // Keep in step with `COMPUTE_WORKGROUP_SIZE`.
             layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
             layout(binding = 0, set = 0) buffer P {
                 vec2 p[];
@@ -70,13 +208,75 @@ pub mod update_cs {
             layout(local_size_x = 64, local_size_y = 1, local_size_z = 1) in;
             layout(binding = 2, set = 0) buffer R {
                 // Had to pass in [radius, 0.0] to satisfy my `get_compute_command_buffer` function
+                // The second component holds `RigidBody::get_gpu_flags`.
                 vec2 r[];
             } radii;
 
+            layout(binding = 3, set = 0) buffer C {
+                Constraint c[];
+            } constraints;
+
+            // Append buffer: `count` is reset by the host after the events are read back.
+            layout(binding = 4, set = 0) buffer EC {
+                uint count;
+            } event_count;
+
+            layout(binding = 5, set = 0) buffer E {
+                CollisionEvent e[];
+            } events;
+
+            // [category, mask] of each object, as in `CollisionFilter`.
+            layout(binding = 6, set = 0) buffer F {
+                uvec2 f[];
+            } filters;
+
+            // Pairs of object indices that never collide.
+            layout(binding = 7, set = 0) buffer N {
+                uvec2 n[];
+            } never_collide;
+
+            void push_collision_event(uint body_a, int body_b, vec2 point, vec2 normal, float impulse) {
+                uint idx = atomicAdd(event_count.count, 1);
+                if (idx < events.e.length()) {
+                    events.e[idx] = CollisionEvent(body_a, body_b, point, normal, impulse);
+                }
+            }
+
+            bool has_flag(uint object_id, uint flag) {
+                return (uint(radii.r[object_id][1]) & flag) != 0;
+            }
+
+            // Mirrors `PairFilter::should_collide`.
+            bool should_collide(uint object_one_id, uint object_two_id) {
+                uvec2 filter_one = filters.f[object_one_id];
+                uvec2 filter_two = filters.f[object_two_id];
+                if ((filter_one.x & filter_two.y) == 0 || (filter_two.x & filter_one.y) == 0) {
+                    return false;
+                }
+                for (uint idx = 0; idx < num_never_collide; idx++) {
+                    uvec2 pair = never_collide.n[idx];
+                    if ((pair.x == object_one_id && pair.y == object_two_id) || (pair.x == object_two_id && pair.y == object_one_id)) {
+                        return false;
+                    }
+                }
+                return true;
+            }
+
+            bool collides_with_world(uint object_id) {
+                return (filters.f[object_id].y & WORLD_COLLISION_CATEGORY) != 0;
+            }
+
             bool do_objects_collide(uint ref_object_id, uint other_object_id) {
                 if (ref_object_id == other_object_id) {
                     return false;
                 }
+                // Sensor overlaps are found on the host after readback; they never push bodies apart.
+                if (has_flag(ref_object_id, FLAG_SENSOR) || has_flag(other_object_id, FLAG_SENSOR)) {
+                    return false;
+                }
+                if (!should_collide(ref_object_id, other_object_id)) {
+                    return false;
+                }
 
                 vec2 vector_between_coms = positions.p[ref_object_id] - positions.p[other_object_id]; 
                 float distance_between_coms = length(vector_between_coms);
@@ -90,6 +290,76 @@ pub mod update_cs {
                 return false;
             }
 
+            // Mirrors `RigidBody::get_inverse_mass`: unit density, so mass is the area.
+            float inverse_mass(float radius) {
+                return 1. / (PI * radius * radius);
+            }
+
+            // Springs push on the velocity; distance joints and taut ropes remove our share of the
+            // relative speed along the axis and of the length error.
+            void apply_constraints(uint object_id) {
+                for (uint idx = 0; idx < num_constraints; idx++) {
+                    Constraint constraint = constraints.c[idx];
+                    bool is_body_a = constraint.body_a == object_id;
+                    bool is_body_b = constraint.body_b == int(object_id);
+                    if (!is_body_a && !is_body_b) {
+                        continue;
+                    }
+
+                    vec2 other_position = constraint.anchor;
+                    vec2 other_velocity = vec2(0.);
+                    float other_inverse_mass = 0.;
+                    if (is_body_b || constraint.body_b >= 0) {
+                        uint other_id = is_body_a ? uint(constraint.body_b) : constraint.body_a;
+                        other_position = positions.p[other_id];
+                        other_velocity = velocities.v[other_id];
+                        other_inverse_mass = inverse_mass(radii.r[other_id][0]);
+                    }
+                    float own_inverse_mass = inverse_mass(radii.r[object_id][0]);
+
+                    vec2 vector_to_other = other_position - positions.p[object_id];
+                    float distance = length(vector_to_other);
+                    if (distance <= 0.) {
+                        continue;
+                    }
+                    vec2 unit_normal = vector_to_other / distance;
+                    float share = own_inverse_mass / (own_inverse_mass + other_inverse_mass);
+                    float relative_speed = dot(other_velocity - velocities.v[object_id], unit_normal);
+
+                    if (constraint.kind == CONSTRAINT_SPRING) {
+                        float force = constraint.params[1] * (distance - constraint.params[0]) + constraint.params[2] * relative_speed;
+                        velocities.v[object_id] += unit_normal * force * dt * own_inverse_mass;
+                        continue;
+                    }
+
+                    float length_error = distance - constraint.params[0];
+                    if (constraint.kind == CONSTRAINT_ROPE && length_error <= 0.) {
+                        continue;
+                    }
+                    if (constraint.kind == CONSTRAINT_DISTANCE || relative_speed < 0.) {
+                        velocities.v[object_id] += unit_normal * relative_speed * share;
+                    }
+                    positions.p[object_id] += unit_normal * length_error * correction_factor * share;
+                }
+            }
+
+            // Penetration beyond `slop`, scaled by `correction_factor`, that should be removed this step.
+            float penetration_correction(float penetration) {
+                return max(penetration - slop, 0.) * correction_factor;
+            }
+
+            // Removes `correction` of penetration along `direction`: split impulse moves the body right away,
+            // Baumgarte turns it into velocity that the integration below applies.
+            void correct_penetration(uint object_id, vec2 direction, float correction) {
+                if (position_correction_method == POSITION_CORRECTION_SPLIT_IMPULSE) {
+                    positions.p[object_id] += direction * correction;
+                } else if (position_correction_method == POSITION_CORRECTION_BAUMGARTE) {
+                    velocities.v[object_id] += direction * (correction / dt);
+                }
+            }
+
+            // Reflects the relative velocity along the normal, as a perfectly elastic collision. Unlike the CPU
+            // backend's `ContactSolver` there is no restitution, accumulation or warm starting.
             void resolve_object_collision(uint object_one_id, uint object_two_id) {
                 vec2 object_one_position = positions.p[object_one_id];
                 vec2 object_one_velocity = velocities.v[object_one_id];
@@ -99,9 +369,12 @@ pub mod update_cs {
                 float object_two_radius = radii.r[object_two_id][0];
 
                 vec2 com_distance_vector = object_one_position - object_two_position;
-                float overlapping_distance = length(com_distance_vector) - object_one_radius - object_two_radius;
-                float inclination_angle_of_vector = atan(com_distance_vector.y, com_distance_vector.x);
-                vec2 distance_to_move_either =  vec2(overlapping_distance * cos(inclination_angle_of_vector), overlapping_distance * sin(inclination_angle_of_vector));
+                float com_distance = length(com_distance_vector);
+                vec2 unit_normal = com_distance > 0. ? com_distance_vector / com_distance : vec2(0., -1.);
+                float penetration = object_one_radius + object_two_radius - com_distance;
+                float inverse_mass_one = inverse_mass(object_one_radius);
+                float inverse_mass_two = inverse_mass(object_two_radius);
+                float mass_share_one = inverse_mass_one / (inverse_mass_one + inverse_mass_two);
 
                 vec2 updated_velocity_one = object_one_velocity - (dot(object_one_velocity - object_two_velocity, object_one_position - object_two_position))
                 / (pow(length(object_two_position - object_one_position), 2)) * (object_one_position - object_two_position);
@@ -109,35 +382,156 @@ pub mod update_cs {
                 / (pow(length(object_two_position - object_one_position), 2)) * (object_two_position - object_one_position);
 
 
-                positions.p[object_one_id] += -1. * distance_to_move_either;
-                positions.p[object_two_id] += 1. * distance_to_move_either;
+                // Both invocations of the pair resolve it, so only the lower index reports it.
+                if (object_one_id < object_two_id) {
+                    float impulse = length(updated_velocity_one - object_one_velocity) / inverse_mass_one;
+                    push_collision_event(object_one_id, int(object_two_id), object_one_position - unit_normal * object_one_radius, -unit_normal, impulse);
+                }
+
                 velocities.v[object_one_id] = updated_velocity_one;
                 velocities.v[object_two_id] = updated_velocity_two;
+                // The other invocation of this pair corrects the other object, so only move our own.
+                correct_penetration(object_one_id, unit_normal, penetration_correction(penetration) * mass_share_one);
             }
 
             void check_and_resolve_world_collision(uint object_id) {
+                if (!collides_with_world(object_id)) {
+                    return;
+                }
                 vec2 object_position = positions.p[object_id];
                 float object_radius = radii.r[object_id][0];
-                bool crossed_lateral = false;
-                bool crossed_vertical = false;
+                vec2 penetration = abs(object_position) + vec2(abs(object_radius)) - vec2(1.);
 
-                if ((abs(object_position[0]) + abs(object_radius)) > 1.) {
-                    crossed_lateral = true;
+                float object_mass = 1. / inverse_mass(object_radius);
+                if (penetration.x > 0.) {
+                    float wall_side = sign(object_position.x);
+                    float impulse = 0.;
+                    if (velocities.v[object_id].x * wall_side > 0.) {
+                        impulse = 2. * abs(velocities.v[object_id].x) * object_mass;
+                        velocities.v[object_id].x *= -1.;
+                    }
+                    vec2 normal = vec2(wall_side, 0.);
+                    push_collision_event(object_id, wall_side > 0. ? -2 : -1, object_position + normal * object_radius, normal, impulse);
+                    correct_penetration(object_id, -normal, penetration_correction(penetration.x));
                 }
-                if ((abs(object_position[1]) + abs(object_radius)) > 1.) {
-                    crossed_vertical = true;
+                if (penetration.y > 0.) {
+                    float wall_side = sign(object_position.y);
+                    float impulse = 0.;
+                    if (velocities.v[object_id].y * wall_side > 0.) {
+                        impulse = 2. * abs(velocities.v[object_id].y) * object_mass;
+                        velocities.v[object_id].y *= -1.;
+                    }
+                    vec2 normal = vec2(0., wall_side);
+                    push_collision_event(object_id, wall_side > 0. ? -4 : -3, object_position + normal * object_radius, normal, impulse);
+                    correct_penetration(object_id, -normal, penetration_correction(penetration.y));
                 }
+            }
+
+            // Mirrors `ccd::circle_time_of_impact`: fraction of the step at which two swept circles first touch.
+            float circle_time_of_impact(uint object_id, uint other_id) {
+                vec2 offset = positions.p[other_id] - positions.p[object_id];
+                vec2 relative_displacement = (velocities.v[other_id] - velocities.v[object_id]) * dt;
+                float radius_sum = radii.r[object_id][0] + radii.r[other_id][0];
 
-                if (crossed_lateral) {
-                    velocities.v[object_id].x *= -1.;
+                float a = dot(relative_displacement, relative_displacement);
+                float b = 2. * dot(offset, relative_displacement);
+                float c = dot(offset, offset) - radius_sum * radius_sum;
+                if (c <= 0. || b >= 0. || a <= 0.) {
+                    return 1.;
                 }
-                if (crossed_vertical) {
-                    velocities.v[object_id].y *= -1.;
+                float discriminant = b * b - 4. * a * c;
+                if (discriminant < 0.) {
+                    return 1.;
+                }
+                float time_of_impact = (-b - sqrt(discriminant)) / (2. * a);
+                return time_of_impact >= 0. && time_of_impact <= 1. ? time_of_impact : 1.;
+            }
+
+            // Mirrors `ccd::wall_time_of_impact` for the [-1, 1] world.
+            float wall_time_of_impact(uint object_id, vec2 displacement) {
+                float radius = radii.r[object_id][0];
+                float earliest = 1.;
+                for (int axis = 0; axis < 2; axis++) {
+                    if (displacement[axis] == 0.) {
+                        continue;
+                    }
+                    float limit = sign(displacement[axis]) * (1. - radius) - positions.p[object_id][axis];
+                    float time_of_impact = limit / displacement[axis];
+                    if (time_of_impact >= 0. && time_of_impact <= 1.) {
+                        earliest = min(earliest, time_of_impact);
+                    }
                 }
+                return earliest;
+            }
+
+            bool needs_ccd(uint object_id) {
+                return has_flag(object_id, FLAG_BULLET) || length(velocities.v[object_id]) * dt > ccd_threshold * radii.r[object_id][0];
+            }
+
+            // Mirrors `ccd::earliest_impact`: fraction of the step a body may move before it first touches something,
+            // with what it touches as a `CollisionEvent::body_b`, the normal towards it and its velocity and inverse
+            // mass, which are zero for walls. Swept bodies are checked against walls and every body, the others only
+            // against swept bodies. Returns 1 when nothing is hit.
+            float earliest_impact(uint object_id, out int target, out vec2 normal, out vec2 other_velocity, out float other_inverse_mass) {
+                bool is_swept = needs_ccd(object_id);
+                float earliest = 1.;
+                target = 0;
+                normal = vec2(0.);
+                other_velocity = vec2(0.);
+                other_inverse_mass = 0.;
+                if (is_swept && collides_with_world(object_id)) {
+                    vec2 displacement = velocities.v[object_id] * dt;
+                    for (int axis = 0; axis < 2; axis++) {
+                        vec2 axis_displacement = vec2(0.);
+                        axis_displacement[axis] = displacement[axis];
+                        float time_of_impact = wall_time_of_impact(object_id, axis_displacement);
+                        if (time_of_impact < earliest) {
+                            earliest = time_of_impact;
+                            normal = sign(axis_displacement);
+                            target = -1 - 2 * axis - (normal[axis] > 0. ? 1 : 0);
+                        }
+                    }
+                }
+                if (has_flag(object_id, FLAG_SENSOR)) {
+                    return earliest;
+                }
+                for (uint other_idx = 0; other_idx < num_objects; other_idx++) {
+                    if (other_idx == object_id || !(is_swept || needs_ccd(other_idx)) || has_flag(other_idx, FLAG_SENSOR) || !should_collide(object_id, other_idx)) {
+                        continue;
+                    }
+                    float time_of_impact = circle_time_of_impact(object_id, other_idx);
+                    if (time_of_impact < earliest) {
+                        earliest = time_of_impact;
+                        target = int(other_idx);
+                        vec2 offset = positions.p[other_idx] + velocities.v[other_idx] * dt * time_of_impact
+                            - positions.p[object_id] - velocities.v[object_id] * dt * time_of_impact;
+                        normal = normalize(offset);
+                        other_velocity = velocities.v[other_idx];
+                        other_inverse_mass = inverse_mass(radii.r[other_idx][0]);
+                    }
+                }
+                return earliest;
+            }
+
+            // Velocity right after a swept contact. Reflects the approach along the normal, like `resolve_object_collision`,
+            // and keeps the motion along the contact.
+            vec2 velocity_after_impact(uint object_id, vec2 normal, vec2 other_velocity, float other_inverse_mass) {
+                vec2 velocity = velocities.v[object_id];
+                float approach_speed = dot(velocity - other_velocity, normal);
+                if (approach_speed <= 0.) {
+                    return velocity;
+                }
+                float own_inverse_mass = inverse_mass(radii.r[object_id][0]);
+                float impulse = 2. * approach_speed / (own_inverse_mass + other_inverse_mass);
+                return velocity - normal * impulse * own_inverse_mass;
             }
 
             void main() {
                 uint x = gl_GlobalInvocationID.x;
+                // The last workgroup runs past the end of the buffers.
+                if (x >= num_objects) {
+                    return;
+                }
 
                 // Check and resolve object-world collisions.
                 check_and_resolve_world_collision(x);
@@ -150,8 +544,29 @@ pub mod update_cs {
                     }
                 }
 
-                // Update state as usual. First-order Euler, or something.
-                vec2 position_change = vec2(velocities.v[x] * dt);
+                apply_constraints(x);
+
+                // Update state as usual. First-order Euler, or something. Swept bodies move up to their first contact,
+                // then spend the rest of the step moving with the velocity the contact leaves them.
+                int target;
+                vec2 normal;
+                vec2 other_velocity;
+                float other_inverse_mass;
+                float time_of_impact = earliest_impact(x, target, normal, other_velocity, other_inverse_mass);
+                vec2 position_change = velocities.v[x] * dt * time_of_impact;
+                if (time_of_impact < 1.) {
+                    vec2 velocity_after = velocity_after_impact(x, normal, other_velocity, other_inverse_mass);
+                    float impulse = length(velocity_after - velocities.v[x]) / inverse_mass(radii.r[x][0]);
+                    vec2 point = positions.p[x] + position_change + normal * radii.r[x][0];
+                    // Both ends of a swept pair may report it, so the lower index goes first and the CPU keeps one.
+                    if (target >= 0 && uint(target) < x) {
+                        push_collision_event(uint(target), int(x), point, -normal, impulse);
+                    } else {
+                        push_collision_event(x, target, point, normal, impulse);
+                    }
+                    velocities.v[x] = velocity_after;
+                    position_change += velocities.v[x] * dt * (1. - time_of_impact);
+                }
                 vec2 velocity_change = vec2(0, gravity * dt);
                 positions.p[x] += position_change;
                 velocities.v[x] += velocity_change;
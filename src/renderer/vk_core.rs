@@ -5,19 +5,23 @@
 use crate::renderer::shaders::update_cs;
 use crate::renderer::shaders::update_cs::ComputeConstants;
 use crate::renderer::vk_core::command_buffer::allocator::StandardCommandBufferAllocator;
-use crate::renderer::vk_primitives::get_graphics_pipeline;
-use std::collections::HashMap;
+use crate::renderer::vk_primitives::{get_graphics_pipeline, get_instanced_graphics_pipeline};
+use ecolor::Color32;
+use std::collections::VecDeque;
 use std::sync::Arc;
 use std::time::Instant;
 use tracing::{error, info};
-use vulkano::buffer::{BufferContents, Subbuffer};
+use vulkano::buffer::{Buffer, BufferContents, BufferCreateInfo, BufferUsage, Subbuffer};
 use vulkano::pipeline::graphics::vertex_input::Vertex;
 
 use vulkano::command_buffer::{self, CommandBufferExecFuture, PrimaryAutoCommandBuffer};
 use vulkano::device::{Device, Queue};
 use vulkano::image::Image;
 use vulkano::instance::{Instance, InstanceCreateInfo};
-use vulkano::memory::allocator::{FreeListAllocator, GenericMemoryAllocator};
+use vulkano::memory::allocator::{
+    AllocationCreateInfo, FreeListAllocator, GenericMemoryAllocator, MemoryTypeFilter,
+};
+use vulkano::pipeline::graphics::color_blend::AttachmentBlend;
 use vulkano::pipeline::graphics::viewport::Viewport;
 use vulkano::pipeline::GraphicsPipeline;
 use vulkano::render_pass::{Framebuffer, RenderPass};
@@ -29,24 +33,57 @@ use vulkano::swapchain::{
 use vulkano::sync::future::{FenceSignalFuture, JoinFuture};
 use vulkano::sync::GpuFuture;
 use vulkano::{sync, Validated, VulkanError, VulkanLibrary};
-use winit::dpi::Size;
-use winit::event::{Event, WindowEvent};
+use winit::dpi::{LogicalSize, Size};
+use winit::event::{ElementState, Event, MouseButton, MouseScrollDelta, WindowEvent};
 use winit::event_loop::EventLoop;
-use winit::window::{Window, WindowBuilder};
+use winit::window::{Fullscreen, Window, WindowBuilder};
 
-use crate::physics::scene::{self, Scene};
+use crate::core::keymap::{Action, Keymap, DEFAULT_KEYMAP_PATH};
+use crate::physics::circle::Circle;
+use crate::physics::constraint::GpuConstraint;
+use crate::physics::event::{CollisionPhase, GpuCollisionEvent};
+use crate::physics::mouse_joint::MouseJointInfo;
+use crate::physics::query::QueryFilter;
+use crate::physics::rigidbody::{RBid, RigidBody};
+use crate::physics::scene::{self, Backend, BodyStyle, Rendering, Scene};
 use crate::{FVec2, WINDOW_LENGTH};
 
+use super::camera::{Camera, CAMERA_ZOOM_STEP};
+use super::frame::{BodyInstances, FrameDraw, FrameRing, InstanceBuffers, InstanceDraw};
+use super::overlay::{HelpOverlay, TimeScaleIndicator, TimelineSlider};
 use super::vk_primitives::{
     self, create_command_buffer_allocator, create_memory_allocator, create_swapchain_and_images,
-    get_compute_command_buffer, get_framebuffers, get_render_command_buffers, get_render_pass,
-    get_required_extensions,
+    get_compute_command_buffer, get_framebuffers, get_render_pass, get_required_extensions,
+};
+use super::vk_procedural_functions::{
+    generate_polygon_triangles, generate_rectangle_triangles, Polygon,
 };
 
-const WINDOW_DIMENSION: Size = Size::Physical(winit::dpi::PhysicalSize {
-    width: WINDOW_LENGTH as u32,
-    height: WINDOW_LENGTH as u32,
+#[doc = "Initial size of the window, in logical pixels so it looks the same on HiDPI displays"]
+const WINDOW_DIMENSION: Size = Size::Logical(LogicalSize {
+    width: WINDOW_LENGTH as f64,
+    height: WINDOW_LENGTH as f64,
 });
+const MIN_WINDOW_DIMENSION: Size = Size::Logical(LogicalSize {
+    width: 160.,
+    height: 160.,
+});
+
+#[doc = "Playback speeds the time scale steps through, slowest first"]
+const TIME_SCALES: [f32; 7] = [0.1, 0.25, 0.5, 1., 2., 5., 10.];
+#[doc = "Index of 1x in `TIME_SCALES`"]
+const DEFAULT_TIME_SCALE_INDEX: usize = 3;
+const DEFAULT_STEP_BATCH: u32 = 10;
+#[doc = "Steps of cursor motion averaged into the velocity of a thrown body"]
+const FLING_SAMPLE_STEPS: u64 = 50;
+const FLING_MAX_SPEED: f32 = 20.;
+const SPAWN_RADIUS: f32 = 0.04;
+#[doc = "Scroll distance of touchpads and other pixel-precise devices that counts as one line"]
+const SCROLL_PIXELS_PER_LINE: f32 = 40.;
+#[doc = "Sides of the polygon every body is drawn as when rendering from the GPU buffers"]
+const INSTANCE_POLYGON_SIDES: u8 = 32;
+#[doc = "`local_size_x` of `update_cs`. One invocation steps one body."]
+const COMPUTE_WORKGROUP_SIZE: u32 = 64;
 
 type SwapchainJoinFuture = JoinFuture<Box<dyn GpuFuture>, SwapchainAcquireFuture>;
 type FenceFuture = FenceSignalFuture<PresentFuture<CommandBufferExecFuture<SwapchainJoinFuture>>>;
@@ -63,6 +100,13 @@ pub struct WindowEventHandler {
 
     perf_stats: PerformanceStats,
     sim_flags: SimulationFlags,
+    playback: Playback,
+    #[doc = "Last cursor position, in normalized device coordinates"]
+    cursor_position: FVec2,
+    pointer: Pointer,
+    camera: Camera,
+    frame_ring: FrameRing,
+    keymap: Keymap,
 }
 
 #[derive(Clone)]
@@ -70,11 +114,108 @@ pub struct RuntimeBuffers {
     pub objects_positions: Subbuffer<[[f32; 2]]>,
     pub objects_velocities: Subbuffer<[[f32; 2]]>,
     pub objects_radii: Subbuffer<[[f32; 2]]>,
+    pub objects_colors: Subbuffer<[[u8; 4]]>,
+    pub constraints: Subbuffer<[GpuConstraint]>,
+    pub collision_event_count: Subbuffer<[u32]>,
+    pub collision_events: Subbuffer<[GpuCollisionEvent]>,
+    pub collision_filters: Subbuffer<[[u32; 2]]>,
+    pub never_collide: Subbuffer<[[u32; 2]]>,
 }
 
 struct SimulationFlags {
     recreate_swapchain_flag: bool,
-    is_paused_flag: bool,
+    #[doc = "The timeline slider is being dragged"]
+    is_scrubbing_flag: bool,
+    #[doc = "Pause as soon as two bodies, or a body and a wall, start touching"]
+    run_until_collision_flag: bool,
+    show_help_flag: bool,
+    #[doc = "The right mouse button is held, dragging the view"]
+    is_panning_flag: bool,
+}
+
+#[doc = "How many physics steps run per frame. Fractional time scales carry the remainder over to the next frame."]
+struct Playback {
+    time_scale_index: usize,
+    step_accumulator: f32,
+    #[doc = "Steps taken by the step-N control"]
+    step_batch: u32,
+}
+
+impl Playback {
+    fn new() -> Self {
+        Self {
+            time_scale_index: DEFAULT_TIME_SCALE_INDEX,
+            step_accumulator: 0.,
+            step_batch: DEFAULT_STEP_BATCH,
+        }
+    }
+    fn get_time_scale(&self) -> f32 {
+        TIME_SCALES[self.time_scale_index]
+    }
+    fn change_time_scale(&mut self, faster: bool) {
+        self.time_scale_index = match faster {
+            true => (self.time_scale_index + 1).min(TIME_SCALES.len() - 1),
+            false => self.time_scale_index.saturating_sub(1),
+        };
+        info!("time scale set to {}x", self.get_time_scale());
+    }
+    #[doc = "Number of steps to run this frame"]
+    fn take_steps(&mut self) -> u32 {
+        self.step_accumulator += self.get_time_scale();
+        let steps = self.step_accumulator.floor();
+        self.step_accumulator -= steps;
+        steps as u32
+    }
+}
+
+#[doc = "Body held with the mouse, and what clicking does"]
+struct Pointer {
+    held: Option<RBid>,
+    #[doc = "Recent cursor positions in world coordinates, with the step count they were seen at"]
+    trail: VecDeque<(u64, FVec2)>,
+    mouse_joint: MouseJointInfo,
+    #[doc = "Circle spawned by clicking empty space. Its position is replaced by the cursor's."]
+    spawn_preset: Circle,
+}
+
+impl Pointer {
+    fn new() -> Self {
+        Self {
+            held: None,
+            trail: VecDeque::new(),
+            mouse_joint: MouseJointInfo::default(),
+            spawn_preset: Circle {
+                radius: SPAWN_RADIUS,
+                color: Color32::LIGHT_GREEN,
+                ..Default::default()
+            },
+        }
+    }
+    fn push_trail(&mut self, step: u64, point: FVec2) {
+        self.trail.push_back((step, point));
+        while self
+            .trail
+            .front()
+            .is_some_and(|(sample_step, _)| *sample_step + FLING_SAMPLE_STEPS < step)
+        {
+            self.trail.pop_front();
+        }
+    }
+    #[doc = "Velocity of the cursor over the last `FLING_SAMPLE_STEPS` steps, in world units per simulated second. None when the scene has not stepped while the body was held."]
+    fn get_fling_velocity(&self, step: u64, dt: f32, point: FVec2) -> Option<FVec2> {
+        let (first_step, first_point) = self
+            .trail
+            .iter()
+            .find(|(sample_step, _)| *sample_step + FLING_SAMPLE_STEPS >= step)?;
+        if *first_step >= step {
+            return None;
+        }
+        let velocity = (point - *first_point) * (1. / ((step - first_step) as f32 * dt));
+        Some(match velocity.magnitude() > FLING_MAX_SPEED {
+            true => velocity.get_unit() * FLING_MAX_SPEED,
+            false => velocity,
+        })
+    }
 }
 
 struct PerformanceStats {
@@ -94,9 +235,17 @@ struct RenderContext {
     cs: Arc<ShaderModule>,
     compute_command_buffer: Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>>,
     vs: Arc<ShaderModule>,
+    instance_vs: Arc<ShaderModule>,
+    circle_vs: Arc<ShaderModule>,
     fs: Arc<ShaderModule>,
     render_pass: Arc<RenderPass>,
     graphics_pipeline: Arc<GraphicsPipeline>,
+    instanced_pipeline: Arc<GraphicsPipeline>,
+    #[doc = "Polygon of radius 1 around the origin, drawn once per body by `instanced_pipeline`"]
+    unit_polygon: Subbuffer<[UnitVertex]>,
+    circle_pipeline: Arc<GraphicsPipeline>,
+    #[doc = "The square [-1,1]², drawn once per body by `circle_pipeline`"]
+    unit_square: Subbuffer<[UnitVertex]>,
 
     swapchain: Arc<Swapchain>,
     framebuffers: Vec<Arc<Framebuffer>>,
@@ -115,12 +264,14 @@ impl RenderContext {
     ) -> Self {
         let cs = update_cs::load(vk_ctx.get_device().clone()).unwrap();
         let vs = super::shaders::vs::load(vk_ctx.get_device().clone()).unwrap();
+        let instance_vs = super::shaders::instance_vs::load(vk_ctx.get_device().clone()).unwrap();
+        let circle_vs = super::shaders::circle_vs::load(vk_ctx.get_device().clone()).unwrap();
         let fs = super::shaders::fs::load(vk_ctx.get_device().clone()).unwrap();
         let (swapchain, images) = create_swapchain_and_images(window_ctx, vk_ctx, event_loop);
         let render_pass = get_render_pass(vk_ctx.get_device().clone(), &swapchain);
         let framebuffers = get_framebuffers(&images, &render_pass);
         let viewport = Viewport {
-            extent: [WINDOW_LENGTH; 2],
+            extent: window_ctx.window.inner_size().into(),
             ..Default::default()
         };
 
@@ -131,28 +282,50 @@ impl RenderContext {
             render_pass.clone(),
             viewport.clone(),
         );
-        let compute_command_buffer = get_compute_command_buffer(
-            vk_ctx.clone(),
-            cs.clone(),
-            vec![
-                runtime_buffers.objects_positions.clone(),
-                runtime_buffers.objects_velocities.clone(),
-                // FIX: Remove need for the radii buffer to be [f32; 2].
-                runtime_buffers.objects_radii.clone(),
-            ],
-            Some(push_constants),
-            [push_constants.num_objects, 1, 1],
-        )
-        .unwrap()
-        .build()
-        .unwrap();
+        let instanced_pipeline = get_instanced_graphics_pipeline(
+            vk_ctx.get_device().clone(),
+            instance_vs.clone(),
+            fs.clone(),
+            render_pass.clone(),
+            viewport.clone(),
+            None,
+        );
+        let circle_pipeline = get_instanced_graphics_pipeline(
+            vk_ctx.get_device().clone(),
+            circle_vs.clone(),
+            fs.clone(),
+            render_pass.clone(),
+            viewport.clone(),
+            Some(AttachmentBlend::alpha()),
+        );
+        let unit_polygon = create_unit_shape(
+            vk_ctx,
+            generate_polygon_triangles(
+                INSTANCE_POLYGON_SIDES,
+                FVec2::new(0., 0.).to_custom_vertex(None),
+                1.,
+                Color32::WHITE,
+            ),
+        );
+        let unit_square = create_unit_shape(
+            vk_ctx,
+            generate_rectangle_triangles(FVec2::new(-1., -1.), FVec2::new(1., 1.), Color32::WHITE),
+        );
+        let compute_command_buffer =
+            build_compute_command_buffer(vk_ctx, cs.clone(), runtime_buffers, push_constants);
 
         Self {
             cs,
             vs,
+            instance_vs,
+            circle_vs,
             fs,
             render_pass,
             graphics_pipeline,
+            instanced_pipeline,
+            unit_polygon,
+            circle_pipeline,
+            unit_square,
             viewport,
             swapchain,
             framebuffers,
@@ -160,12 +333,161 @@ impl RenderContext {
             compute_command_buffer,
         }
     }
+    #[doc = "Rebuilds the swapchain, framebuffers and pipeline at the window's current size"]
+    fn recreate_swapchain(&mut self, device: Arc<Device>, window: &Window) {
+        let (swapchain, images) = self
+            .swapchain
+            .recreate(SwapchainCreateInfo {
+                image_extent: window.inner_size().into(),
+                ..self.swapchain.create_info()
+            })
+            .expect("failed to recreate swapchain");
+        self.swapchain = swapchain;
+        self.framebuffers = get_framebuffers(&images, &self.render_pass);
+        self.images = images;
+        self.viewport.extent = window.inner_size().into();
+        self.graphics_pipeline = get_graphics_pipeline(
+            device.clone(),
+            self.vs.clone(),
+            self.fs.clone(),
+            self.render_pass.clone(),
+            self.viewport.clone(),
+        );
+        self.instanced_pipeline = get_instanced_graphics_pipeline(
+            device.clone(),
+            self.instance_vs.clone(),
+            self.fs.clone(),
+            self.render_pass.clone(),
+            self.viewport.clone(),
+            None,
+        );
+        self.circle_pipeline = get_instanced_graphics_pipeline(
+            device,
+            self.circle_vs.clone(),
+            self.fs.clone(),
+            self.render_pass.clone(),
+            self.viewport.clone(),
+            Some(AttachmentBlend::alpha()),
+        );
+    }
     fn viewport(&self) -> Viewport {
         self.viewport.clone()
     }
 }
 
+#[doc = "Uploads the positions of `shape` as the shape drawn once per body"]
+fn create_unit_shape(vk_ctx: &VulkanoContext, shape: Polygon) -> Subbuffer<[UnitVertex]> {
+    Buffer::from_iter(
+        vk_ctx.get_memory_allocator(),
+        BufferCreateInfo {
+            usage: BufferUsage::VERTEX_BUFFER,
+            ..Default::default()
+        },
+        AllocationCreateInfo {
+            memory_type_filter: MemoryTypeFilter::PREFER_DEVICE
+                | MemoryTypeFilter::HOST_SEQUENTIAL_WRITE,
+            ..Default::default()
+        },
+        shape
+            .into_iter()
+            .flatten()
+            .map(|vertex| UnitVertex {
+                offset: vertex.position_in,
+            })
+            .collect::<Vec<_>>(),
+    )
+    .expect("could not allocate a unit shape")
+}
+
+fn build_compute_command_buffer(
+    vk_ctx: &VulkanoContext,
+    cs: Arc<ShaderModule>,
+    runtime_buffers: &RuntimeBuffers,
+    push_constants: ComputeConstants,
+) -> Arc<PrimaryAutoCommandBuffer<Arc<StandardCommandBufferAllocator>>> {
+    get_compute_command_buffer(
+        vk_ctx.clone(),
+        cs,
+        vec![
+            runtime_buffers.objects_positions.clone().into_bytes(),
+            runtime_buffers.objects_velocities.clone().into_bytes(),
+            // FIX: Remove need for the radii buffer to be [f32; 2].
+            runtime_buffers.objects_radii.clone().into_bytes(),
+            runtime_buffers.constraints.clone().into_bytes(),
+            runtime_buffers.collision_event_count.clone().into_bytes(),
+            runtime_buffers.collision_events.clone().into_bytes(),
+            runtime_buffers.collision_filters.clone().into_bytes(),
+            runtime_buffers.never_collide.clone().into_bytes(),
+        ],
+        Some(push_constants),
+        [
+            push_constants.num_objects.div_ceil(COMPUTE_WORKGROUP_SIZE),
+            1,
+            1,
+        ],
+    )
+    .unwrap()
+    .build()
+    .unwrap()
+}
+
 impl WindowEventHandler {
+    #[doc = "Recreates the storage buffers, descriptor set and compute command buffer after bodies were spawned or despawned"]
+    fn rebuild_compute_resources(&mut self, scene: &mut Scene) {
+        // NOTE: The old buffers may hold steps that were not read back yet.
+        scene.read_back();
+        self.runtime_buffers =
+            scene.return_compute_shader_buffers(self.vk_ctx.get_memory_allocator());
+        self.render_ctx.compute_command_buffer = build_compute_command_buffer(
+            &self.vk_ctx,
+            self.render_ctx.cs.clone(),
+            &self.runtime_buffers,
+            scene.get_compute_constants(),
+        );
+        self.frame_ring.invalidate();
+    }
+
+    #[doc = "Whether bodies are drawn straight from the compute buffers rather than read back every step"]
+    fn is_resident(scene: &Scene) -> bool {
+        scene.get_backend() == Backend::Gpu
+            && matches!(scene.get_rendering(), Rendering::Resident { .. })
+    }
+
+    #[doc = "Blocks until the GPU has finished every frame in flight, as they may be drawing from the compute buffers"]
+    fn wait_for_frames(&self) {
+        for fence in self.fences.iter().flatten() {
+            fence.wait(None).unwrap();
+        }
+    }
+
+    #[doc = "Width over height of the window's drawable area"]
+    fn get_aspect_ratio(&self) -> f32 {
+        let size = self.window_ctx.window.inner_size();
+        size.width as f32 / size.height.max(1) as f32
+    }
+
+    // NOTE: A minimized window has no area to draw to, so the swapchain is left alone, and the
+    // flag stays set, until it is restored.
+    fn recreate_swapchain(&mut self) {
+        let size = self.window_ctx.window.inner_size();
+        if size.width == 0 || size.height == 0 {
+            return;
+        }
+        self.render_ctx
+            .recreate_swapchain(self.vk_ctx.device.clone(), &self.window_ctx.window);
+        self.frame_ring.invalidate();
+        self.camera.set_aspect_ratio(self.get_aspect_ratio());
+        self.sim_flags.recreate_swapchain_flag = false;
+    }
+
+    fn toggle_fullscreen(&self) {
+        let window = &self.window_ctx.window;
+        match window.fullscreen() {
+            Some(_) => window.set_fullscreen(None),
+            None => window.set_fullscreen(Some(Fullscreen::Borderless(None))),
+        }
+    }
+
     pub fn new(
         event_loop: &EventLoop<()>,
         runtime_buffers: RuntimeBuffers,
@@ -189,8 +511,19 @@ impl WindowEventHandler {
         let perf_stats = PerformanceStats::new();
         let sim_flags = SimulationFlags {
             recreate_swapchain_flag: false,
-            is_paused_flag: false,
+            is_scrubbing_flag: false,
+            run_until_collision_flag: false,
+            show_help_flag: false,
+            is_panning_flag: false,
         };
+        let frame_ring = FrameRing::new(
+            vk_ctx.get_memory_allocator(),
+            vk_ctx.get_command_buffer_allocator(),
+            vk_ctx.get_queue(),
+        );
+        let mut camera = Camera::default();
+        let size = window_ctx.window.inner_size();
+        camera.set_aspect_ratio(size.width as f32 / size.height.max(1) as f32);
         let frames_in_flight = render_ctx.images.len();
         let fences = vec![None; frames_in_flight];
         let previous_fence_i = 0;
@@ -205,9 +538,172 @@ impl WindowEventHandler {
             perf_stats,
             sim_flags,
             runtime_buffers,
+            playback: Playback::new(),
+            cursor_position: FVec2::default(),
+            pointer: Pointer::new(),
+            camera,
+            frame_ring,
+            keymap: Keymap::load_or_default(DEFAULT_KEYMAP_PATH),
+        }
+    }
+
+    pub fn set_keymap(&mut self, keymap: Keymap) {
+        self.keymap = keymap;
+    }
+
+    #[doc = "Replaces the camera. Its aspect ratio is taken from the window."]
+    pub fn set_camera(&mut self, camera: Camera) {
+        self.camera = camera;
+        self.camera.set_aspect_ratio(self.get_aspect_ratio());
+    }
+
+    #[doc = "Circle spawned by clicking empty space"]
+    pub fn set_spawn_preset(&mut self, preset: Circle) {
+        self.pointer.spawn_preset = preset;
+    }
+
+    pub fn set_mouse_joint_info(&mut self, info: MouseJointInfo) {
+        self.pointer.mouse_joint = info;
+    }
+
+    #[doc = "Number of steps the step-N control advances by"]
+    pub fn set_step_batch(&mut self, steps: u32) {
+        self.playback.step_batch = steps;
+    }
+
+    #[doc = "Runs `steps` physics steps on the scene's backend, whether or not it is paused. Stops early, pausing, if run-until-collision is on and a contact begins."]
+    fn run_steps(&mut self, scene: &mut Scene, steps: u32) {
+        if steps > 0 && Self::is_resident(scene) {
+            self.wait_for_frames();
+        }
+        for _ in 0..steps {
+            if scene.take_body_set_changed() && scene.get_backend() == Backend::Gpu {
+                self.rebuild_compute_resources(scene);
+            }
+            match (scene.get_backend(), scene.get_rendering()) {
+                (Backend::Gpu, Rendering::Readback) => scene.update_with_buffers_once(
+                    self.vk_ctx.get_device(),
+                    self.vk_ctx.get_queue(),
+                    self.render_ctx.compute_command_buffer.clone(),
+                    self.runtime_buffers.clone(),
+                ),
+                (Backend::Gpu, Rendering::Resident { readback_interval }) => {
+                    scene.dispatch_with_buffers_once(
+                        self.vk_ctx.get_device(),
+                        self.vk_ctx.get_queue(),
+                        self.render_ctx.compute_command_buffer.clone(),
+                        self.runtime_buffers.clone(),
+                    );
+                    // NOTE: Contacts are only seen once read back, so run-until-collision reads
+                    // back every step.
+                    if self.sim_flags.run_until_collision_flag
+                        || scene
+                            .get_step_count()
+                            .is_multiple_of(readback_interval.max(1) as u64)
+                    {
+                        scene.read_back();
+                    }
+                }
+                (Backend::Cpu, _) => scene.step_once(),
+            }
+            let collision_began = scene
+                .get_collision_events()
+                .iter()
+                .any(|event| event.phase == CollisionPhase::Begin);
+            if self.sim_flags.run_until_collision_flag && collision_began {
+                self.sim_flags.run_until_collision_flag = false;
+                scene.pause();
+                info!("paused on a collision at step {}", scene.get_step_count());
+                return;
+            }
+        }
+    }
+
+    fn timeline_slider(scene: &Scene) -> TimelineSlider {
+        let timeline = scene.get_timeline();
+        TimelineSlider {
+            start: timeline.get_start().unwrap_or_default(),
+            head: timeline.get_head(),
+            current: scene.get_step_count(),
+        }
+    }
+
+    // NOTE: The flip in `Scene::with_info` is applied to a copy of the bodies, so world
+    // coordinates point y down like normalized device coordinates, and only the camera sits
+    // between them.
+    #[doc = "Cursor position in world coordinates"]
+    fn get_cursor_world(&self) -> FVec2 {
+        self.camera.ndc_to_world(self.cursor_position)
+    }
+
+    #[doc = "Follows the body under the cursor, or stops following when there is none"]
+    fn toggle_follow(&mut self, scene: &mut Scene) {
+        scene.read_back();
+        let hit = scene
+            .query_point(self.get_cursor_world(), &QueryFilter::default())
+            .into_iter()
+            .next();
+        self.camera.set_follow(hit);
+        match hit {
+            Some(id) => info!("following {}", id),
+            None => info!("stopped following"),
+        }
+    }
+
+    #[doc = "Grabs the body under the cursor, or spawns the preset circle when there is none. Clicking a static body does nothing."]
+    fn press_at_cursor(&mut self, scene: &mut Scene) {
+        scene.read_back();
+        let point = self.get_cursor_world();
+        let filter = QueryFilter {
+            include_sensors: false,
+            ..Default::default()
+        };
+        let hits = scene.query_point(point, &filter);
+        if hits.is_empty() {
+            let id = scene.spawn(RigidBody::from(Circle {
+                position: point,
+                ..self.pointer.spawn_preset.clone()
+            }));
+            info!("spawned {} at {}", id, point);
+            return;
+        }
+        let Some(id) = hits.into_iter().find(|id| {
+            scene
+                .body(*id)
+                .is_some_and(|body| body.get_inverse_mass() > 0.)
+        }) else {
+            return;
+        };
+        scene.grab(id, point, self.pointer.mouse_joint);
+        self.pointer.held = Some(id);
+        self.pointer.trail.clear();
+        self.pointer.push_trail(scene.get_step_count(), point);
+    }
+
+    #[doc = "Lets go of the held body, throwing it with the cursor's recent velocity"]
+    fn release_held(&mut self, scene: &mut Scene) {
+        let Some(id) = self.pointer.held.take() else {
+            return;
+        };
+        // NOTE: Restoring a snapshot, e.g. by rewinding, lets go of the body on the scene's side.
+        if scene.get_mouse_joint().is_none() {
+            return;
+        }
+        scene.release();
+        let point = self.get_cursor_world();
+        if let Some(velocity) =
+            self.pointer
+                .get_fling_velocity(scene.get_step_count(), scene.get_dt(), point)
+        {
+            scene.set_velocity(id, velocity);
         }
     }
 
+    #[doc = "Moves the scene `steps` back (negative) or forward along its timeline"]
+    fn scrub_by(scene: &mut Scene, steps: i64) {
+        scene.seek(scene.get_step_count().saturating_add_signed(steps));
+    }
+
     pub fn run_with_scene(mut self, mut scene: Scene, event_loop: EventLoop<()>) {
         let library = VulkanLibrary::new().expect("can't find vulkan library");
         let physical_device = vk_primitives::select_physical_device(&self.window_ctx, &event_loop);
@@ -237,104 +733,278 @@ impl WindowEventHandler {
     }
 
     pub fn handle_window_event(&mut self, scene: &mut Scene, event: &Event<()>) {
-        let mut os_scancodes: HashMap<char, u32> = HashMap::new();
-        #[cfg(target_arch = "x86_64")]
-        {
-            os_scancodes.insert('q', 16);
-            os_scancodes.insert('p', 25);
-            os_scancodes.insert('r', 19);
-        }
-        // #[cfg(target_arch = "aarch64")]
-        {
-            // FIX: Add scancodes for MacOS
-            // os_scancodes.insert('q', 16);
-            // os_scancodes.insert('p', 25);
-            // os_scancodes.insert('r', 19);
-        }
-
         match event {
             Event::WindowEvent {
                 event: WindowEvent::CloseRequested,
                 ..
             } => std::process::exit(0),
             Event::WindowEvent {
-                event: WindowEvent::KeyboardInput { input, .. },
+                event: WindowEvent::Resized(_) | WindowEvent::ScaleFactorChanged { .. },
                 ..
-            } => match input.scancode {
-                /* Code for q */
-                val if val == os_scancodes.get(&'q').unwrap().clone() => {
-                    dbg!("fps avg: {}", self.perf_stats.avg());
-                    info!("10 fps samples: {:?}", self.perf_stats.framerates);
-                    std::process::exit(0);
+            } => self.sim_flags.recreate_swapchain_flag = true,
+            Event::WindowEvent {
+                event: WindowEvent::CursorMoved { position, .. },
+                ..
+            } => {
+                let size = self.window_ctx.window.inner_size();
+                let previous_position = self.cursor_position;
+                self.cursor_position = FVec2::new(
+                    2. * position.x as f32 / size.width as f32 - 1.,
+                    2. * position.y as f32 / size.height as f32 - 1.,
+                );
+                if self.sim_flags.is_panning_flag {
+                    self.camera.pan_by(self.cursor_position - previous_position);
                 }
-                val if val == os_scancodes.get(&'p').unwrap().clone() => {
-                    self.sim_flags.is_paused_flag = true;
-                    return;
+                if self.sim_flags.is_scrubbing_flag {
+                    let step = Self::timeline_slider(scene).step_along(self.cursor_position.x);
+                    scene.seek(step);
                 }
-                val if val == os_scancodes.get(&'r').unwrap().clone() => {
-                    self.sim_flags.is_paused_flag = false
+                if self.pointer.held.is_some() {
+                    let point = self.get_cursor_world();
+                    scene.drag_to(point);
+                    self.pointer.push_trail(scene.get_step_count(), point);
+                }
+            }
+            Event::WindowEvent {
+                event: WindowEvent::MouseWheel { delta, .. },
+                ..
+            } => {
+                let lines = match delta {
+                    MouseScrollDelta::LineDelta(_, y) => *y,
+                    MouseScrollDelta::PixelDelta(position) => {
+                        position.y as f32 / SCROLL_PIXELS_PER_LINE
+                    }
+                };
+                self.camera
+                    .zoom_at(self.cursor_position, CAMERA_ZOOM_STEP.powf(lines));
+            }
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Right,
+                        ..
+                    },
+                ..
+            } => self.sim_flags.is_panning_flag = *state == ElementState::Pressed,
+            Event::WindowEvent {
+                event:
+                    WindowEvent::MouseInput {
+                        state,
+                        button: MouseButton::Left,
+                        ..
+                    },
+                ..
+            } => match state {
+                ElementState::Pressed => {
+                    let slider_step = match scene.is_paused() {
+                        true => Self::timeline_slider(scene).step_at(self.cursor_position),
+                        false => None,
+                    };
+                    match slider_step {
+                        Some(step) => {
+                            self.sim_flags.is_scrubbing_flag = true;
+                            scene.seek(step);
+                        }
+                        None => self.press_at_cursor(scene),
+                    }
+                }
+                ElementState::Released => {
+                    self.sim_flags.is_scrubbing_flag = false;
+                    self.release_held(scene);
                 }
-                _ => info!("{} was pressed", input.scancode),
             },
-            Event::MainEventsCleared => {
-                if self.sim_flags.is_paused_flag {
+            Event::WindowEvent {
+                event: WindowEvent::KeyboardInput { input, .. },
+                ..
+            } if input.state == ElementState::Pressed => {
+                let Some(key) = input.virtual_keycode else {
+                    info!("scancode {} was pressed", input.scancode);
                     return;
+                };
+                let Some(action) = self.keymap.get_action(key) else {
+                    info!("{:?} was pressed", key);
+                    return;
+                };
+                let interval = scene.get_timeline().get_info().keyframe_interval as i64;
+                match action {
+                    Action::Quit => {
+                        info!("fps avg: {}", self.perf_stats.avg());
+                        info!("10 fps samples: {:?}", self.perf_stats.framerates);
+                        std::process::exit(0);
+                    }
+                    Action::Pause => scene.pause(),
+                    Action::Resume => scene.resume(),
+                    Action::RewindKeyframe => Self::scrub_by(scene, -interval),
+                    Action::ForwardKeyframe => Self::scrub_by(scene, interval),
+                    Action::RewindFar => Self::scrub_by(scene, -10 * interval),
+                    Action::ForwardFar => Self::scrub_by(scene, 10 * interval),
+                    Action::StepOnce => {
+                        scene.pause();
+                        self.run_steps(scene, 1);
+                    }
+                    Action::StepBatch => {
+                        scene.pause();
+                        self.run_steps(scene, self.playback.step_batch);
+                    }
+                    Action::RunUntilCollision => {
+                        self.sim_flags.run_until_collision_flag = true;
+                        scene.resume();
+                    }
+                    Action::SlowDown => self.playback.change_time_scale(false),
+                    Action::SpeedUp => self.playback.change_time_scale(true),
+                    Action::ToggleFollow => self.toggle_follow(scene),
+                    Action::ResetCamera => self.camera.reset(),
+                    Action::ToggleFullscreen => self.toggle_fullscreen(),
+                    Action::ToggleHelp => {
+                        self.sim_flags.show_help_flag = !self.sim_flags.show_help_flag
+                    }
+                }
+            }
+            Event::MainEventsCleared => {
+                if scene.take_body_set_changed() && scene.get_backend() == Backend::Gpu {
+                    self.rebuild_compute_resources(scene);
+                }
+                // NOTE: Frames are still drawn while paused, so scrubbing the timeline shows up.
+                if !scene.is_paused() {
+                    let steps = self.playback.take_steps();
+                    self.run_steps(scene, steps);
+                }
+                let is_resident = Self::is_resident(scene);
+                // NOTE: Edits made since the last step, e.g. scrubbing while paused, only reach the
+                // buffers drawn from when uploaded here. A paused scene is read back so it can be
+                // inspected exactly.
+                if is_resident {
+                    self.wait_for_frames();
+                    scene.write_to_buffers(&self.runtime_buffers);
+                    if scene.is_paused() {
+                        scene.read_back();
+                    }
+                }
+                self.camera.update(scene);
+                // NOTE: A moving camera moves the world under a still cursor, so the held body's
+                // target is refreshed every frame.
+                if self.pointer.held.is_some() {
+                    let point = self.get_cursor_world();
+                    if scene
+                        .get_mouse_joint()
+                        .is_some_and(|joint| joint.target != point)
+                    {
+                        scene.drag_to(point);
+                    }
                 }
 
-                scene.update_with_buffers(
-                    self.vk_ctx.get_device(),
-                    self.vk_ctx.get_queue(),
-                    self.render_ctx.compute_command_buffer.clone(),
-                    self.runtime_buffers.clone(),
-                );
-
-                let (new_swapchain, new_images) = self
-                    .render_ctx
-                    .swapchain
-                    .recreate(SwapchainCreateInfo {
-                        image_extent: self.window_ctx.window.inner_size().into(),
-                        ..self.render_ctx.swapchain.create_info()
-                    })
-                    .expect("failed to recreate swapchain: {e}");
-                self.render_ctx.swapchain = new_swapchain;
-                self.render_ctx.framebuffers =
-                    get_framebuffers(&new_images, &self.render_ctx.render_pass);
-                self.render_ctx.viewport.extent = self.window_ctx.window.inner_size().into();
-                self.render_ctx.graphics_pipeline = get_graphics_pipeline(
-                    self.vk_ctx.device.clone(),
-                    self.render_ctx.vs.clone(),
-                    self.render_ctx.fs.clone(),
-                    self.render_ctx.render_pass.clone(),
-                    self.render_ctx.viewport.clone(),
-                );
-
-                let vertex_buffer =
-                    scene.return_objects_as_vertex_buffer(self.vk_ctx.device.clone());
-                let command_buffers = get_render_command_buffers(
-                    &self.vk_ctx.command_buffer_allocator,
-                    &self.vk_ctx.queue,
-                    &self.render_ctx.graphics_pipeline,
-                    &self.render_ctx.framebuffers,
-                    &vertex_buffer,
-                )
-                .unwrap();
+                if self.sim_flags.recreate_swapchain_flag {
+                    self.recreate_swapchain();
+                    if self.sim_flags.recreate_swapchain_flag {
+                        return;
+                    }
+                }
 
+                let mut overlay = match scene.is_paused() {
+                    true => Self::timeline_slider(scene).to_vertices(),
+                    false => vec![],
+                };
+                if self.playback.time_scale_index != DEFAULT_TIME_SCALE_INDEX {
+                    let indicator = TimeScaleIndicator {
+                        time_scale: self.playback.get_time_scale(),
+                        min: TIME_SCALES[0],
+                        max: TIME_SCALES[TIME_SCALES.len() - 1],
+                    };
+                    overlay.extend(indicator.to_vertices());
+                }
+                if self.sim_flags.show_help_flag {
+                    overlay.extend(
+                        HelpOverlay::from_keymap(
+                            &self.keymap,
+                            self.playback.step_batch,
+                            self.camera.get_aspect_ratio(),
+                        )
+                        .to_vertices(),
+                    );
+                }
                 let (image_i, suboptimal, acquire_future) =
                     match swapchain::acquire_next_image(self.render_ctx.swapchain.clone(), None)
                         .map_err(Validated::unwrap)
                     {
                         Ok(r) => r,
                         Err(VulkanError::OutOfDate) => {
-                            // self.recreate_swapchain_flag = true;
+                            self.sim_flags.recreate_swapchain_flag = true;
                             return;
                         }
                         Err(e) => panic!("failed to acquire the next image: {e}"),
                     };
 
-                self.sim_flags.recreate_swapchain_flag = if suboptimal { true } else { false };
+                if suboptimal {
+                    self.sim_flags.recreate_swapchain_flag = true;
+                }
                 if let Some(image_fence) = &self.fences[image_i as usize] {
                     image_fence.wait(None).unwrap();
                 }
+
+                // NOTE: Bodies are drawn as instances when they stay on the GPU or are shaded as
+                // SDF circles, and tessellated on the CPU otherwise.
+                let body_style = scene.get_body_style();
+                let instance_draw = (is_resident || body_style != BodyStyle::Polygon).then(|| {
+                    let size = self.window_ctx.window.inner_size();
+                    InstanceDraw {
+                        count: scene.bodies().len() as u32,
+                        style: body_style,
+                        pixel_size: self.camera.get_pixel_size(size.width.min(size.height)),
+                        is_resident,
+                    }
+                });
+                match instance_draw {
+                    Some(InstanceDraw {
+                        is_resident: false, ..
+                    }) => self.frame_ring.set_instances(scene.bodies()),
+                    _ => self.frame_ring.set_instances(&[]),
+                }
+                let vertices = self.frame_ring.begin_frame();
+                match instance_draw {
+                    Some(_) => scene.write_link_vertices(vertices),
+                    None => scene.write_vertices(vertices),
+                }
+                let world_vertex_count = vertices.len() as u32;
+                vertices.extend(overlay);
+                let draw = FrameDraw {
+                    instances: instance_draw,
+                    world_vertex_count,
+                    vertex_count: vertices.len() as u32,
+                    view_projection: self.camera.get_view_constants().view_projection,
+                };
+                self.frame_ring.upload(image_i as usize).unwrap();
+                let instances = instance_draw.and_then(|instance_draw| {
+                    let buffers = match instance_draw.is_resident {
+                        true => InstanceBuffers::from(&self.runtime_buffers),
+                        false => self.frame_ring.get_instance_buffers(image_i as usize)?,
+                    };
+                    let (pipeline, unit_shape) = match instance_draw.style {
+                        BodyStyle::Polygon => (
+                            &self.render_ctx.instanced_pipeline,
+                            &self.render_ctx.unit_polygon,
+                        ),
+                        BodyStyle::Sdf { .. } => (
+                            &self.render_ctx.circle_pipeline,
+                            &self.render_ctx.unit_square,
+                        ),
+                    };
+                    Some(BodyInstances {
+                        pipeline: pipeline.clone(),
+                        unit_shape: unit_shape.clone(),
+                        buffers,
+                    })
+                });
+                let command_buffer = self
+                    .frame_ring
+                    .get_command_buffer(
+                        image_i as usize,
+                        draw,
+                        &self.render_ctx.graphics_pipeline,
+                        &self.render_ctx.framebuffers[image_i as usize],
+                        instances.as_ref(),
+                    )
+                    .unwrap();
                 let previous_fence = match self.fences[self.previous_fence_i as usize].clone() {
                     None => {
                         let mut now = sync::now(self.vk_ctx.device.clone());
@@ -346,10 +1016,7 @@ impl WindowEventHandler {
 
                 let future = previous_fence
                     .join(acquire_future)
-                    .then_execute(
-                        self.vk_ctx.queue.clone(),
-                        command_buffers[image_i as usize].clone(),
-                    )
+                    .then_execute(self.vk_ctx.queue.clone(), command_buffer)
                     .unwrap()
                     .then_swapchain_present(
                         self.vk_ctx.queue.clone(),
@@ -363,8 +1030,7 @@ impl WindowEventHandler {
                 self.fences[image_i as usize] = match future.map_err(Validated::unwrap) {
                     Ok(value) => Some(Arc::new(value)),
                     Err(VulkanError::OutOfDate) => {
-                        // FIX: crashes on MoltenVK
-                        // self.recreate_swapchain_flag = true;
+                        self.sim_flags.recreate_swapchain_flag = true;
                         None
                     }
                     Err(e) => {
@@ -397,7 +1063,8 @@ impl WindowContext {
             WindowBuilder::new()
                 .with_title("vulkys")
                 .with_inner_size(WINDOW_DIMENSION)
-                .with_resizable(false)
+                .with_min_inner_size(MIN_WINDOW_DIMENSION)
+                .with_resizable(true)
                 .build(&event_loop)
                 .unwrap(),
         );
@@ -471,3 +1138,34 @@ pub struct CustomVertex {
     #[format(R8G8B8A8_UNORM)]
     pub color: [u8; 4],
 }
+
+#[doc = "Vertex of the polygon drawn once per body, relative to the body's centre and radius"]
+#[derive(BufferContents, Vertex, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct UnitVertex {
+    #[format(R32G32_SFLOAT)]
+    pub offset: FVec2,
+}
+
+// NOTE: The instance layouts describe elements of `RuntimeBuffers`, which are bound as they are.
+#[derive(BufferContents, Vertex, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct InstancePosition {
+    #[format(R32G32_SFLOAT)]
+    pub instance_position: [f32; 2],
+}
+
+#[doc = "Radius, followed by the GPU flags the vertex shader ignores"]
+#[derive(BufferContents, Vertex, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct InstanceRadius {
+    #[format(R32G32_SFLOAT)]
+    pub instance_radius: [f32; 2],
+}
+
+#[derive(BufferContents, Vertex, Debug, Clone, Copy, PartialEq)]
+#[repr(C)]
+pub struct InstanceColor {
+    #[format(R8G8B8A8_UNORM)]
+    pub instance_color: [u8; 4],
+}
@@ -18,11 +18,11 @@ use vulkano::pipeline::PipelineBindPoint;
 
 use vulkano::image::view::ImageView;
 use vulkano::image::{Image, ImageUsage};
-use vulkano::pipeline::graphics::color_blend::ColorBlendAttachmentState;
+use vulkano::pipeline::graphics::color_blend::{AttachmentBlend, ColorBlendAttachmentState};
 use vulkano::pipeline::graphics::input_assembly::{InputAssemblyState, PrimitiveTopology};
 use vulkano::pipeline::graphics::multisample::MultisampleState;
 use vulkano::pipeline::graphics::rasterization::RasterizationState;
-use vulkano::pipeline::graphics::vertex_input::VertexDefinition;
+use vulkano::pipeline::graphics::vertex_input::{VertexDefinition, VertexInputState};
 use vulkano::pipeline::graphics::viewport::{Viewport, ViewportState};
 use vulkano::pipeline::graphics::GraphicsPipelineCreateInfo;
 use vulkano::pipeline::layout::PipelineDescriptorSetLayoutCreateInfo;
@@ -30,13 +30,19 @@ use vulkano::pipeline::ComputePipeline;
 use vulkano::pipeline::Pipeline;
 use vulkano::pipeline::{GraphicsPipeline, PipelineLayout, PipelineShaderStageCreateInfo};
 use vulkano::render_pass::{Framebuffer, FramebufferCreateInfo, RenderPass, Subpass};
-use vulkano::shader::ShaderModule;
+use vulkano::shader::{EntryPoint, ShaderModule};
 use vulkano::swapchain::{Surface, Swapchain, SwapchainCreateInfo};
 use vulkano::VulkanLibrary;
 use winit::event_loop::EventLoop;
 
-use super::shaders::update_cs;
-use super::vk_core::{CustomVertex, VulkanoContext, WindowContext};
+use super::camera::Camera;
+use super::frame::{BodyInstances, FrameDraw};
+use super::shaders::{circle_vs::CircleConstants, update_cs, vs::ViewConstants};
+use super::vk_core::{
+    CustomVertex, InstanceColor, InstancePosition, InstanceRadius, UnitVertex, VulkanoContext,
+    WindowContext,
+};
+use crate::physics::scene::BodyStyle;
 use vulkano::command_buffer::allocator::{
     StandardCommandBufferAllocator, StandardCommandBufferAllocatorCreateInfo,
 };
@@ -292,44 +298,106 @@ pub fn create_command_buffer_allocator(device: Arc<Device>) -> StandardCommandBu
     )
 }
 
-pub fn get_render_command_buffers(
+#[doc = "Records drawing the bodies in `instances` and the vertices described by `draw` from `vertex_buffer` into `framebuffer`. The command buffer can be submitted again for as long as `draw` and the instance buffers stay the same."]
+pub fn get_render_command_buffer(
     command_buffer_allocator: &StandardCommandBufferAllocator,
     queue: &Arc<Queue>,
     pipeline: &Arc<GraphicsPipeline>,
-    framebuffers: &Vec<Arc<Framebuffer>>,
+    framebuffer: &Arc<Framebuffer>,
     vertex_buffer: &Subbuffer<[CustomVertex]>,
-) -> anyhow::Result<Vec<Arc<PrimaryAutoCommandBuffer>>> {
-    framebuffers
-        .iter()
-        .map(
-            |framebuffer| -> anyhow::Result<Arc<PrimaryAutoCommandBuffer>> {
-                let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
-                    command_buffer_allocator,
-                    queue.queue_family_index(),
-                    command_buffer::CommandBufferUsage::MultipleSubmit,
-                )
-                .unwrap();
-
-                command_buffer_builder
-                    .begin_render_pass(
-                        RenderPassBeginInfo {
-                            clear_values: vec![Some([0.01, 0.01, 0.01, 1.0].into())],
-                            ..command_buffer::RenderPassBeginInfo::framebuffer(framebuffer.clone())
-                        },
-                        SubpassBeginInfo {
-                            contents: command_buffer::SubpassContents::Inline,
-                            ..Default::default()
-                        },
-                    )?
-                    .bind_pipeline_graphics(pipeline.clone())?
-                    .bind_vertex_buffers(0, vertex_buffer.clone())?
-                    .draw(vertex_buffer.len() as u32, 1, 0, 0)?
-                    .end_render_pass(SubpassEndInfo::default())?;
+    instances: Option<&BodyInstances>,
+    draw: FrameDraw,
+) -> anyhow::Result<Arc<PrimaryAutoCommandBuffer>> {
+    let FrameDraw {
+        instances: instance_draw,
+        world_vertex_count,
+        vertex_count,
+        view_projection,
+    } = draw;
+    let mut command_buffer_builder = AutoCommandBufferBuilder::primary(
+        command_buffer_allocator,
+        queue.queue_family_index(),
+        command_buffer::CommandBufferUsage::MultipleSubmit,
+    )?;
+    command_buffer_builder.begin_render_pass(
+        RenderPassBeginInfo {
+            clear_values: vec![Some([0.01, 0.01, 0.01, 1.0].into())],
+            ..command_buffer::RenderPassBeginInfo::framebuffer(framebuffer.clone())
+        },
+        SubpassBeginInfo {
+            contents: command_buffer::SubpassContents::Inline,
+            ..Default::default()
+        },
+    )?;
+    // NOTE: Bodies come first, so constraint lines are drawn over them.
+    if let (Some(instance_draw), Some(instances)) = (instance_draw, instances) {
+        if instance_draw.count > 0 {
+            let buffers = &instances.buffers;
+            let layout = instances.pipeline.layout().clone();
+            command_buffer_builder
+                .bind_pipeline_graphics(instances.pipeline.clone())?
+                .bind_vertex_buffers(
+                    0,
+                    (
+                        instances.unit_shape.clone(),
+                        buffers.positions.clone(),
+                        buffers.radii.clone(),
+                        buffers.colors.clone(),
+                    ),
+                )?;
+            match instance_draw.style {
+                BodyStyle::Polygon => command_buffer_builder.push_constants(
+                    layout,
+                    0,
+                    ViewConstants { view_projection },
+                )?,
+                BodyStyle::Sdf { outline } => command_buffer_builder.push_constants(
+                    layout,
+                    0,
+                    CircleConstants {
+                        view_projection,
+                        outline_color: outline
+                            .map_or([0; 4], |outline| outline.color.to_array())
+                            .map(|channel| channel as f32 / 255.),
+                        outline_width: outline.map_or(0., |outline| outline.width),
+                        pixel_size: instance_draw.pixel_size,
+                    },
+                )?,
+            };
+            command_buffer_builder.draw(
+                instances.unit_shape.len() as u32,
+                instance_draw.count,
+                0,
+                0,
+            )?;
+        }
+    }
+    command_buffer_builder
+        .bind_pipeline_graphics(pipeline.clone())?
+        .bind_vertex_buffers(0, vertex_buffer.clone())?;
+    if world_vertex_count > 0 {
+        command_buffer_builder
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                ViewConstants { view_projection },
+            )?
+            .draw(world_vertex_count, 1, 0, 0)?;
+    }
+    // NOTE: Vertices past `world_vertex_count` are overlays, drawn in normalized device
+    // coordinates regardless of the camera.
+    if vertex_count > world_vertex_count {
+        command_buffer_builder
+            .push_constants(
+                pipeline.layout().clone(),
+                0,
+                Camera::get_screen_view_constants(),
+            )?
+            .draw(vertex_count - world_vertex_count, 1, world_vertex_count, 0)?;
+    }
+    command_buffer_builder.end_render_pass(SubpassEndInfo::default())?;
 
-                Ok(command_buffer_builder.build()?)
-            },
-        )
-        .collect()
+    Ok(command_buffer_builder.build()?)
 }
 
 pub fn get_graphics_pipeline(
@@ -340,10 +408,59 @@ pub fn get_graphics_pipeline(
     viewport: Viewport,
 ) -> Arc<GraphicsPipeline> {
     let vs = vertex_shader.entry_point("main").unwrap();
-    let fs = fragment_shader.entry_point("main").unwrap();
     let vertex_shader_state = CustomVertex::per_vertex()
         .definition(&vs.info().input_interface)
         .unwrap();
+    build_graphics_pipeline(
+        device,
+        vs,
+        fragment_shader,
+        render_pass,
+        viewport,
+        vertex_shader_state,
+        None,
+    )
+}
+
+#[doc = "Pipeline drawing a `UnitVertex` shape once per body, scaled and placed by the per-instance position, radius and color buffers. `blend` is needed by shaders that fade edges out."]
+pub fn get_instanced_graphics_pipeline(
+    device: Arc<Device>,
+    vertex_shader: Arc<ShaderModule>,
+    fragment_shader: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+    blend: Option<AttachmentBlend>,
+) -> Arc<GraphicsPipeline> {
+    let vs = vertex_shader.entry_point("main").unwrap();
+    let vertex_shader_state = [
+        UnitVertex::per_vertex(),
+        InstancePosition::per_instance(),
+        InstanceRadius::per_instance(),
+        InstanceColor::per_instance(),
+    ]
+    .definition(&vs.info().input_interface)
+    .unwrap();
+    build_graphics_pipeline(
+        device,
+        vs,
+        fragment_shader,
+        render_pass,
+        viewport,
+        vertex_shader_state,
+        blend,
+    )
+}
+
+fn build_graphics_pipeline(
+    device: Arc<Device>,
+    vs: EntryPoint,
+    fragment_shader: Arc<ShaderModule>,
+    render_pass: Arc<RenderPass>,
+    viewport: Viewport,
+    vertex_shader_state: VertexInputState,
+    blend: Option<AttachmentBlend>,
+) -> Arc<GraphicsPipeline> {
+    let fs = fragment_shader.entry_point("main").unwrap();
     let stages = [
         PipelineShaderStageCreateInfo::new(vs),
         PipelineShaderStageCreateInfo::new(fs),
@@ -376,7 +493,10 @@ pub fn get_graphics_pipeline(
             color_blend_state: Some(
                 vulkano::pipeline::graphics::color_blend::ColorBlendState::with_attachment_states(
                     subpass.num_color_attachments(),
-                    ColorBlendAttachmentState::default(),
+                    ColorBlendAttachmentState {
+                        blend,
+                        ..Default::default()
+                    },
                 ),
             ),
             subpass: Some(subpass.into()),
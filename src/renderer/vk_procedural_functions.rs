@@ -48,6 +48,32 @@ pub fn generate_polygon_triangles(
     triangles
 }
 
+#[doc = "Thin rectangle from `from` to `to`, as two triangles"]
+pub fn generate_line_triangles(from: FVec2, to: FVec2, width: f32, with_color: Color32) -> Polygon {
+    let direction = to - from;
+    if direction.magnitude() <= f32::EPSILON {
+        return vec![];
+    }
+
+    let offset = direction.get_orthogonal_unit() * (width / 2.);
+    let corners = [from + offset, to + offset, to - offset, from - offset]
+        .map(|corner| corner.to_custom_vertex(Some(with_color)));
+    vec![
+        [corners[0].clone(), corners[1].clone(), corners[2].clone()],
+        [corners[0].clone(), corners[2].clone(), corners[3].clone()],
+    ]
+}
+
+#[doc = "Axis-aligned rectangle from `min` to `max`, as two triangles"]
+pub fn generate_rectangle_triangles(min: FVec2, max: FVec2, with_color: Color32) -> Polygon {
+    let corners = [min, FVec2::new(max.x, min.y), max, FVec2::new(min.x, max.y)]
+        .map(|corner| corner.to_custom_vertex(Some(with_color)));
+    vec![
+        [corners[0].clone(), corners[1].clone(), corners[2].clone()],
+        [corners[0].clone(), corners[2].clone(), corners[3].clone()],
+    ]
+}
+
 pub trait PolygonMethods {
     fn destructure_into_list(&self) -> Vec<CustomVertex>;
 }